@@ -1,2 +1,2 @@
-pub mod scc;
+pub use abbs_meta_scc as scc;
 pub mod solv;
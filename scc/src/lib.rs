@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+
+/// The dependencies of a package, by name.
+pub type DepGetter<'a> = dyn Fn(&str) -> Vec<String> + 'a;
+
+#[derive(Default)]
+struct TState {
+    lowlink: HashMap<String, usize>,
+    index: HashMap<String, usize>,
+    on_stack: HashSet<String>,
+    stack: Vec<String>,
+}
+
+/// Tarjan's strongly connected components search over the graph reachable from
+/// `packages`. A component comes after every component it depends on.
+pub fn tarjan_search(packages: &[String], get_deps: &DepGetter) -> Vec<Vec<String>> {
+    let mut state = TState::default();
+    let mut results = Vec::new();
+    for package in packages {
+        if !state.index.contains_key(package) {
+            strongly_connected(package, get_deps, &mut state, &mut results);
+        }
+    }
+
+    results
+}
+
+fn strongly_connected(
+    v: &str,
+    get_deps: &DepGetter,
+    state: &mut TState,
+    results: &mut Vec<Vec<String>>,
+) {
+    // assign the next visiting index
+    let index = state.index.len();
+    state.index.insert(v.to_string(), index);
+    state.lowlink.insert(v.to_string(), index);
+    state.on_stack.insert(v.to_string());
+    state.stack.push(v.to_string());
+    // Look for adjacent nodes (dependencies)
+    for d in get_deps(v) {
+        if !state.index.contains_key(&d) {
+            // recurse on unvisited packages
+            strongly_connected(&d, get_deps, state, results);
+            let lowlink = state.lowlink[v].min(state.lowlink[&d]);
+            state.lowlink.insert(v.to_string(), lowlink);
+        } else if state.on_stack.contains(&d) {
+            // adjacent package is in the stack which means it is part of a loop
+            let lowlink = state.lowlink[v].min(state.index[&d]);
+            state.lowlink.insert(v.to_string(), lowlink);
+        }
+    }
+
+    // if this is a root vertex, the stack down to it is one component
+    if state.lowlink[v] == state.index[v] {
+        let mut result = Vec::new();
+        while let Some(w) = state.stack.pop() {
+            state.on_stack.remove(&w);
+            let done = w == v;
+            result.push(w);
+            if done {
+                break;
+            }
+        }
+        results.push(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_sort() {
+        let results = tarjan_search(&["a".to_string()], &|d| match d {
+            "a" => vec!["b".to_string()],
+            "b" => vec!["c".to_string(), "d".to_string()],
+            _ => vec![],
+        });
+        assert_eq!(
+            results,
+            vec![
+                vec!["c".to_string()],
+                vec!["d".to_string()],
+                vec!["b".to_string()],
+                vec!["a".to_string()]
+            ]
+        )
+    }
+
+    #[test]
+    fn test_cycle_sort() {
+        // a -> b -> c -> a, with c also depending on b
+        let results = tarjan_search(&["a".to_string()], &|d| match d {
+            "a" => vec!["b".to_string()],
+            "b" => vec!["c".to_string()],
+            "c" => vec!["a".to_string(), "b".to_string(), "d".to_string()],
+            _ => vec![],
+        });
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], vec!["d".to_string()]);
+        let mut cycle = results[1].clone();
+        cycle.sort();
+        assert_eq!(
+            cycle,
+            vec!["a".to_string(), "b".to_string(), "c".to_string()]
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use abbs_meta_tree::tree::Tree;
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let cache_path = std::env::var("CACHE").unwrap_or_else(|_| "tree-cache.json".to_string());
+    let (tree, report) = Tree::from_cached(&PathBuf::from(tree_dir), &PathBuf::from(cache_path))?;
+
+    for path in report.added.iter() {
+        println!("+ {}", path.display());
+    }
+    for path in report.changed.iter() {
+        println!("~ {}", path.display());
+    }
+    for path in report.removed.iter() {
+        println!("- {}", path.display());
+    }
+    for issue in report.load_report.issues.iter() {
+        eprintln!("{}", issue);
+    }
+    println!(
+        "{} packages, {} directories taken from cache",
+        tree.len(),
+        report.unchanged
+    );
+    Ok(())
+}
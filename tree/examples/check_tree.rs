@@ -0,0 +1,17 @@
+use abbs_meta_tree::tree::{Tree, REQUIRING_FIELDS};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+
+    for issue in tree.check_dependencies(&REQUIRING_FIELDS) {
+        println!("{}", issue);
+    }
+    for issue in tree.check_groups() {
+        println!("{}", issue);
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use abbs_meta_tree::tree::{TopicReport, Tree};
+use anyhow::Result;
+use std::path::Path;
+
+/// Two directories holding stable and the topic, with the merge-base in
+/// `BASE_DIR` if set, or with the `git` feature, stable and topic revisions of
+/// the repository at `TREE_DIR`.
+fn compare(stable: &str, topic: &str) -> Result<TopicReport> {
+    if Path::new(stable).is_dir() && Path::new(topic).is_dir() {
+        let stable = Tree::from(Path::new(stable))?;
+        let topic = Tree::from(Path::new(topic))?;
+        return Ok(match std::env::var("BASE_DIR") {
+            Ok(base) => Tree::from(Path::new(&base))?.compare_topic(&stable, &topic),
+            Err(_) => stable.compare_topic(&stable, &topic),
+        });
+    }
+    #[cfg(feature = "git")]
+    {
+        let repo = std::env::var("TREE_DIR")?;
+        let options = Default::default();
+        Ok(Tree::compare_topic_git(
+            Path::new(&repo),
+            stable,
+            topic,
+            &options,
+        )?)
+    }
+    #[cfg(not(feature = "git"))]
+    anyhow::bail!("{} or {} is not a directory", stable, topic)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        anyhow::bail!("usage: compare_topic <stable> <topic>");
+    }
+    let report = compare(&args[0], &args[1])?;
+
+    if std::env::var("JSON").is_ok() {
+        print!("{}", serde_json::to_string(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
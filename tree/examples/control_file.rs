@@ -0,0 +1,25 @@
+use abbs_meta_tree::tree::Tree;
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+    let arch = std::env::var("ARCH").unwrap_or_else(|_| "amd64".to_string());
+
+    let mut names: Vec<String> = std::env::args().skip(1).collect();
+    if names.is_empty() {
+        names = tree.iter().map(|p| p.name.clone()).collect();
+        names.sort();
+    }
+    let stanzas: Vec<String> = names
+        .iter()
+        .filter_map(|n| tree.get(n))
+        .filter_map(|p| p.control(&arch, tree.arches()))
+        .map(|s| s.to_string())
+        .collect();
+
+    print!("{}", stanzas.join("\n"));
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use abbs_meta_tree::tree::{DependencyKind, Tree};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+
+    let arch = std::env::var("ARCH").ok();
+    let report = tree.dependency_cycles(arch.as_deref(), &DependencyKind::ALL);
+    if std::env::var("DOT").is_ok() {
+        print!("{}", report.to_dot());
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
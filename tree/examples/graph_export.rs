@@ -0,0 +1,30 @@
+use abbs_meta_tree::tree::{GraphOptions, Tree};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// TREE_DIR=... FORMAT=dot|graphml|json DEPTH=1 ARCH=amd64 graph_export gnome-shell
+/// TREE_DIR=... SECTION=desktop-kde graph_export
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+
+    let mut roots: Vec<String> = std::env::args().skip(1).collect();
+    if let Ok(section) = std::env::var("SECTION") {
+        roots.extend(tree.under(&section).map(|p| p.name.clone()));
+    }
+    let roots: Vec<&str> = roots.iter().map(|s| s.as_str()).collect();
+    let options = GraphOptions {
+        arch: std::env::var("ARCH").ok(),
+        depth: std::env::var("DEPTH").ok().map(|d| d.parse()).transpose()?,
+        ..Default::default()
+    };
+    let graph = tree.subgraph(&roots, &options);
+
+    match std::env::var("FORMAT").as_deref() {
+        Ok("graphml") => print!("{}", graph.to_graphml()),
+        Ok("json") => print!("{}", graph.to_node_link_json()),
+        _ => print!("{}", graph.to_dot()),
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use abbs_meta_tree::tree::{DependencyKind, Tree};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+
+    let group = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: group_stages <group>"))?;
+    let arch = std::env::var("ARCH").ok();
+    let stages = tree.group_stages(&group, arch.as_deref(), &DependencyKind::ALL)?;
+
+    for (i, stage) in stages.iter().enumerate() {
+        println!("Stage {}:", i + 1);
+        for group in stage.groups.iter() {
+            if group.cycle {
+                println!("  [cycle] {}", group.packages.join(" "));
+            } else {
+                println!("  {}", group.packages.join(" "));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use abbs_meta_tree::schema::TreeDocument;
+use anyhow::Result;
+
+fn main() -> Result<()> {
+    let schema = schemars::schema_for!(TreeDocument);
+
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use abbs_meta_tree::tree::Tree;
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = PathBuf::from(std::env::var("TREE_DIR")?);
+    let rev = std::env::var("REV").unwrap_or_else(|_| "HEAD".to_string());
+    let name = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: package_history <package>"))?;
+
+    for entry in Tree::package_history(&tree_dir, &rev, &name)? {
+        println!(
+            "{} {} {} ({})",
+            &entry.commit[..12],
+            date(entry.time),
+            entry.summary,
+            entry.author
+        );
+        for line in entry.diff.to_string().lines() {
+            println!("    {}", line);
+        }
+        for issue in entry.issues.iter() {
+            println!("    ! {}", issue);
+        }
+    }
+    Ok(())
+}
+
+/// Seconds since the Unix epoch -> YYYY-MM-DD, in UTC.
+fn date(time: i64) -> String {
+    // From Howard Hinnant's civil_from_days
+    let z = time.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
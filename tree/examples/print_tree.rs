@@ -1,11 +1,14 @@
-use abbs_meta_tree::tree::Tree;
+use abbs_meta_tree::tree::{LoadOptions, Tree};
 use anyhow::Result;
 use std::path::PathBuf;
 
 fn main() -> Result<()> {
     let tree_dir = std::env::var("TREE_DIR")?;
     let path = PathBuf::from(tree_dir);
-    let tree = Tree::from(&path).unwrap();
+    let (tree, report) = Tree::load(&path, &LoadOptions::default())?;
+    for issue in report.issues.iter() {
+        eprintln!("{}", issue);
+    }
 
     println!("{:?}", tree);
     Ok(())
@@ -0,0 +1,17 @@
+use abbs_meta_tree::tree::{DependencyKind, Tree};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let path = PathBuf::from(tree_dir);
+    let tree = Tree::from(&path)?;
+
+    let changed: Vec<String> = std::env::args().skip(1).collect();
+    let changed: Vec<&str> = changed.iter().map(|s| s.as_str()).collect();
+    let arch = std::env::var("ARCH").ok();
+    let plan = tree.plan_rebuild(&changed, arch.as_deref(), &DependencyKind::ALL);
+
+    print!("{}", plan);
+    Ok(())
+}
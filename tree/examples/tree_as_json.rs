@@ -1,13 +1,18 @@
-use abbs_meta_tree::tree::Tree;
-use anyhow::Result;
+use abbs_meta_tree::{schema::SchemaVersion, tree::Tree};
+use anyhow::{anyhow, Result};
 use std::path::PathBuf;
 
 fn main() -> Result<()> {
     let tree_dir = std::env::var("TREE_DIR")?;
     let path = PathBuf::from(tree_dir);
-    let tree = Tree::from(&path).unwrap();
+    let tree = Tree::from(&path)?;
 
-    let json = serde_json::to_string(&tree)?;
+    // SCHEMA=v1 for consumers of the old layout
+    let version = match std::env::var("SCHEMA") {
+        Ok(v) => SchemaVersion::parse(&v).ok_or_else(|| anyhow!("Unknown schema {}", v))?,
+        Err(_) => SchemaVersion::default(),
+    };
+    let json = serde_json::to_string(&tree.to_json(version)?)?;
 
     print!("{}", json);
     Ok(())
@@ -0,0 +1,19 @@
+use abbs_meta_tree::tree::{LoadOptions, Tree};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let rev = std::env::var("REV").unwrap_or_else(|_| "HEAD".to_string());
+    let (tree, report) = Tree::load_git(&PathBuf::from(tree_dir), &rev, &LoadOptions::default())?;
+
+    for issue in report.issues.iter() {
+        eprintln!("{}", issue);
+    }
+    let mut names: Vec<_> = tree.iter().map(|p| (&p.name, p.full_version())).collect();
+    names.sort();
+    for (name, version) in names {
+        println!("{} {}", name, version);
+    }
+    Ok(())
+}
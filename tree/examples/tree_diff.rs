@@ -0,0 +1,16 @@
+use abbs_meta_tree::tree::Tree;
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let old = Tree::from(&PathBuf::from(std::env::var("OLD_TREE_DIR")?))?;
+    let new = Tree::from(&PathBuf::from(std::env::var("NEW_TREE_DIR")?))?;
+    let diff = old.diff(&new);
+
+    if std::env::var("JSON").is_ok() {
+        print!("{}", serde_json::to_string(&diff)?);
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use abbs_meta_tree::tree::{Tree, TreeEvent};
+use anyhow::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let tree_dir = std::env::var("TREE_DIR")?;
+    let tree = Tree::from(&PathBuf::from(tree_dir))?;
+    let (_watcher, events) = tree.watch()?;
+
+    for event in events {
+        match event {
+            TreeEvent::Changed { spec_paths, diff } => {
+                for spec_path in spec_paths {
+                    println!("reloaded {}", spec_path.display());
+                }
+                print!("{}", diff);
+            }
+            TreeEvent::GroupsChanged => println!("reloaded groups"),
+            TreeEvent::Issue(issue) => eprintln!("{}", issue),
+            TreeEvent::Error(e) => eprintln!("watch error: {}", e),
+        }
+    }
+    Ok(())
+}
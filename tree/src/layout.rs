@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+/// Categories of AOSC OS package directories, e.g. `extra` in `extra-admin`.
+pub const ABBS_CATEGORIES: [&str; 8] = [
+    "app", "base", "core", "desktop", "extra", "lang", "meta", "runtime",
+];
+
+/// Top-level directories of a tree that never hold packages.
+pub const NON_PACKAGE_DIRS: [&str; 5] = ["assets", "groups", "topics", ".git", ".github"];
+
+/// How a tree is laid out: `<root>/<category>-<section>/<directory>/spec`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TreeLayout {
+    pub categories: Vec<String>,
+    pub non_package_dirs: Vec<String>,
+}
+
+/// Where a package directory lives in the tree.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PackageLocation {
+    pub category: String,
+    pub section: String,
+    pub directory: String,
+}
+
+impl Default for TreeLayout {
+    fn default() -> Self {
+        TreeLayout {
+            categories: ABBS_CATEGORIES.iter().map(|c| c.to_string()).collect(),
+            non_package_dirs: NON_PACKAGE_DIRS.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+}
+
+impl TreeLayout {
+    pub fn with_categories(mut self, categories: &[&str]) -> Self {
+        self.categories = categories.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn with_non_package_dirs(mut self, dirs: &[&str]) -> Self {
+        self.non_package_dirs = dirs.iter().map(|d| d.to_string()).collect();
+        self
+    }
+
+    /// `extra-admin` -> (`extra`, `admin`), if `extra` is a known category.
+    pub fn split_category<'a>(&self, dir_name: &'a str) -> Option<(&'a str, &'a str)> {
+        let (category, section) = dir_name.split_once('-')?;
+        if section.is_empty() || !self.categories.iter().any(|c| c == category) {
+            return None;
+        }
+        Some((category, section))
+    }
+
+    /// Whether a top-level directory of the tree never holds packages.
+    pub fn is_non_package_dir(&self, dir_name: &str) -> bool {
+        self.non_package_dirs.iter().any(|d| d == dir_name)
+    }
+
+    /// Locate a spec from its path relative to the tree `root`.
+    pub fn locate(&self, root: &Path, spec_path: &Path) -> Option<PackageLocation> {
+        let relative = spec_path.strip_prefix(root).ok()?;
+        let components: Vec<&str> = relative
+            .components()
+            .map(|c| match c {
+                Component::Normal(s) => s.to_str(),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        match components.as_slice() {
+            [category_section, directory, "spec"] => {
+                if self.is_non_package_dir(category_section) {
+                    return None;
+                }
+                let (category, section) = self.split_category(category_section)?;
+                Some(PackageLocation {
+                    category: category.to_string(),
+                    section: section.to_string(),
+                    directory: directory.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Locate a spec without knowing the tree root, assuming the usual depth.
+    pub fn locate_spec(&self, spec_path: &Path) -> Option<PackageLocation> {
+        let root = spec_path.parent()?.parent()?.parent()?;
+        self.locate(root, spec_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let layout = TreeLayout::default();
+        let root = Path::new("/home/core-dev/aosc-os-abbs");
+        let location = layout
+            .locate(root, &root.join("extra-admin/packagekit/spec"))
+            .unwrap();
+        assert_eq!(
+            (location.category.as_str(), location.section.as_str()),
+            ("extra", "admin")
+        );
+        assert_eq!(location.directory, "packagekit");
+
+        let location = layout
+            .locate_spec(Path::new("/home/core-dev/tree/app-web/app-foo/spec"))
+            .unwrap();
+        assert_eq!(location.category, "app");
+        assert_eq!(location.section, "web");
+
+        let bad_cases = vec![
+            "groups/plasma/spec",
+            "assets/foo/spec",
+            "foo-bar/baz/spec",
+            "extra-/baz/spec",
+            "extra-admin/spec",
+            "extra-admin/foo/autobuild/spec",
+        ];
+        for case in bad_cases {
+            assert_eq!(layout.locate(root, &root.join(case)), None, "{}", case);
+        }
+
+        let layout = TreeLayout::default().with_categories(&["custom"]);
+        assert!(layout.split_category("custom-admin").is_some());
+        assert!(layout.split_category("extra-admin").is_none());
+    }
+}
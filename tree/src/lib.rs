@@ -1,4 +1,6 @@
+pub mod layout;
 pub mod package;
+pub mod schema;
 pub mod tree;
 
 pub use package::Package;
@@ -0,0 +1,223 @@
+use super::{
+    deps_for_arch, fields::is_noarch, full_version, ArchRegistry, Dependency, FieldValue, Package,
+    KNOWN_FIELDS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A package as autobuild sees it when building for one arch.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ArchPackage {
+    pub name: String,
+    pub arch: String,
+    pub epoch: usize,
+    pub version: String,
+    pub release: String,
+    pub pkg_section: String,
+    pub description: String,
+    /// `false` if `FAIL_ARCH` rules this arch out
+    pub available: bool,
+
+    pub dependencies: Vec<Dependency>,
+    pub build_dependencies: Vec<Dependency>,
+    pub package_suggests: Vec<Dependency>,
+    pub package_provides: Vec<Dependency>,
+    pub recommends: Vec<Dependency>,
+    pub package_replaces: Vec<Dependency>,
+    pub package_breaks: Vec<Dependency>,
+    pub conflicts: Vec<Dependency>,
+
+    /// Fields from `KNOWN_FIELDS` that are set or have a default, without arch suffix
+    pub options: BTreeMap<String, FieldValue>,
+}
+
+impl Package {
+    /// Resolve every per-arch field for `arch`: `FIELD__ARCH` wins over
+    /// `FIELD__GROUP` for any group of `arch` in `arches`, which wins over the plain `FIELD`.
+    pub fn for_arch(&self, arch: &str, arches: &ArchRegistry) -> ArchPackage {
+        let arch = arch.to_lowercase();
+        let resolve = |deps| deps_for_arch(deps, &arch, arches).to_vec();
+        let options = KNOWN_FIELDS
+            .iter()
+            .filter(|f| !f.name.ends_with('*'))
+            .map(|f| f.name)
+            .chain(
+                self.options
+                    .keys()
+                    .map(|k| k.split("__").next().unwrap_or(k)),
+            )
+            .filter_map(|name| Some((name.to_string(), self.option(name, Some(&arch), arches)?)))
+            .collect();
+
+        ArchPackage {
+            name: self.name.clone(),
+            epoch: self.epoch,
+            version: self.version.clone(),
+            release: self.release.clone(),
+            pkg_section: self.pkg_section.clone(),
+            description: self.description.clone(),
+            available: self.is_available_on(&arch),
+            dependencies: resolve(&self.dependencies),
+            build_dependencies: resolve(&self.build_dependencies),
+            package_suggests: resolve(&self.package_suggests),
+            package_provides: resolve(&self.package_provides),
+            recommends: resolve(&self.recommends),
+            package_replaces: resolve(&self.package_replaces),
+            package_breaks: resolve(&self.package_breaks),
+            conflicts: resolve(&self.conflicts),
+            options,
+            arch,
+        }
+    }
+}
+
+impl ArchPackage {
+    /// `epoch:version-release`, see `Package::full_version`
+    pub fn full_version(&self) -> String {
+        full_version(self.epoch, &self.version, &self.release)
+    }
+
+    /// `ABHOST=noarch` on this arch, see `Package::is_noarch`
+    pub fn is_noarch(&self) -> bool {
+        is_noarch(self.options.get("ABHOST"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout::TreeLayout, package::SectionRegistry};
+    use std::{collections::HashMap, path::Path};
+
+    #[test]
+    fn test_arch_overrides() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGVER", "1.0"),
+            ("PKGDES", "Foo"),
+            ("PKGSEC", "admin"),
+            ("PKGDEP", "bar"),
+            ("PKGDEP__RETRO", "bar-lite"),
+            ("PKGDEP__PPC64", "bar-ppc64"),
+            ("BUILDDEP__OPTENV32", "gcc+32"),
+            ("FAIL_ARCH", "!(amd64|ppc64)"),
+            ("NOLTO__RETRO", "1"),
+            ("AB_FLAGS_O3", "1"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let arches = ArchRegistry::default();
+        let sections = SectionRegistry::default();
+        let spec_path = Path::new("/tree/extra-admin/foo/spec");
+        let layout = TreeLayout::default();
+        let pkg =
+            Package::with_registries(&context, spec_path, &layout, &sections, &arches).unwrap();
+
+        let names = |p: &ArchPackage| -> Vec<String> {
+            p.dependencies.iter().map(|d| d.to_string()).collect()
+        };
+        let amd64 = pkg.for_arch("AMD64", &arches);
+        assert_eq!(names(&amd64), vec!["bar"]);
+        // OPTENV32 is a build variant, not a group amd64 belongs to
+        assert!(amd64.build_dependencies.is_empty());
+        assert!(amd64.available);
+        assert_eq!(names(&pkg.for_arch("i486", &arches)), vec!["bar-lite"]);
+        assert_eq!(names(&pkg.for_arch("ppc64", &arches)), vec!["bar-ppc64"]);
+        assert!(pkg
+            .for_arch("riscv64", &arches)
+            .build_dependencies
+            .is_empty());
+        assert!(!pkg.for_arch("riscv64", &arches).available);
+        assert_eq!(amd64.options["NOLTO"], FieldValue::Bool(false));
+        assert_eq!(amd64.options["AB_FLAGS_O3"], FieldValue::Bool(true));
+        assert_eq!(
+            pkg.for_arch("i486", &arches).options["NOLTO"],
+            FieldValue::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_custom_registry() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGVER", "1.0"),
+            ("PKGDES", "Foo"),
+            ("PKGSEC", "admin"),
+            ("PKGDEP", "bar"),
+            ("PKGDEP__NEWWORLD", "bar-new"),
+            ("FAIL_ARCH", "newworld"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let mut arches = ArchRegistry::default();
+        arches
+            .groups
+            .insert("newworld".to_string(), vec!["riscv64".to_string()]);
+        let sections = SectionRegistry::default();
+        let spec_path = Path::new("/tree/extra-admin/foo/spec");
+        let layout = TreeLayout::default();
+        let pkg =
+            Package::with_registries(&context, spec_path, &layout, &sections, &arches).unwrap();
+
+        let riscv64 = pkg.for_arch("riscv64", &arches);
+        assert_eq!(riscv64.dependencies[0].to_string(), "bar-new");
+        assert!(!riscv64.available);
+        assert!(pkg.for_arch("amd64", &arches).available);
+        // Unknown to the built-in registry
+        let builtin = ArchRegistry::default();
+        assert_eq!(
+            pkg.for_arch("riscv64", &builtin).dependencies[0].to_string(),
+            "bar"
+        );
+    }
+
+    #[test]
+    fn test_group_precedence() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGVER", "1.0"),
+            ("PKGDES", "Foo"),
+            ("PKGSEC", "admin"),
+            ("PKGDEP", "bar"),
+            ("PKGDEP__MAINLINE", "bar-mainline"),
+            ("PKGDEP__LOONG", "bar-loong"),
+            ("NOLTO__LOONG", "1"),
+            ("NOLTO__MAINLINE", "0"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        // Sorting before or after `mainline` by name makes no difference
+        let sections = SectionRegistry::default();
+        let spec_path = Path::new("/tree/extra-admin/foo/spec");
+        let layout = TreeLayout::default();
+        for group in ["loong", "newloong"] {
+            let context: HashMap<String, String> = context
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.replace("__LOONG", &format!("__{}", group.to_uppercase())),
+                        v.clone(),
+                    )
+                })
+                .collect();
+            let mut arches = ArchRegistry::default();
+            arches.groups.insert(
+                group.to_string(),
+                vec!["loongarch64".to_string(), "loongson3".to_string()],
+            );
+            assert_eq!(arches.groups_of("loongson3"), vec!["mainline", group]);
+            let pkg =
+                Package::with_registries(&context, spec_path, &layout, &sections, &arches).unwrap();
+
+            let loongson3 = pkg.for_arch("loongson3", &arches);
+            assert_eq!(loongson3.dependencies[0].to_string(), "bar-loong");
+            assert_eq!(loongson3.options["NOLTO"], FieldValue::Bool(true));
+            let amd64 = pkg.for_arch("amd64", &arches);
+            assert_eq!(amd64.dependencies[0].to_string(), "bar-mainline");
+            assert_eq!(amd64.options["NOLTO"], FieldValue::Bool(false));
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use super::AUTOBUILD4_PATH;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BTreeMap, fs, io, path::Path, sync::OnceLock};
+
+/// Architectures shipped by AOSC OS when no autobuild4 checkout is at hand.
+pub const KNOWN_ARCHES: [&str; 15] = [
+    "amd64",
+    "arm64",
+    "armv4",
+    "armv6hf",
+    "armv7hf",
+    "i486",
+    "loongarch64",
+    "loongson2f",
+    "loongson3",
+    "m68k",
+    "mips64r6el",
+    "powerpc",
+    "ppc64",
+    "ppc64el",
+    "riscv64",
+];
+
+/// Arch groups known to autobuild4, when no autobuild4 checkout is at hand.
+pub const ARCH_GROUPS: [(&str, &[&str]); 2] = [
+    (
+        "mainline",
+        &[
+            "amd64",
+            "arm64",
+            "loongarch64",
+            "loongson3",
+            "ppc64el",
+            "riscv64",
+        ],
+    ),
+    (
+        "retro",
+        &[
+            "armv4",
+            "armv6hf",
+            "armv7hf",
+            "i486",
+            "loongson2f",
+            "m68k",
+            "powerpc",
+            "ppc64",
+        ],
+    ),
+];
+
+/// Build variants such as `optenv32`: `FIELD__OPTENV32` applies to the variant
+/// build, not to every arch that has one, so they are never read as arch groups.
+pub const BUILD_VARIANTS: [&str; 1] = ["optenv32"];
+
+/// Known architectures and arch groups, e.g. `retro` -> `[i486, powerpc, ...]`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct ArchRegistry {
+    pub arches: Vec<String>,
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for ArchRegistry {
+    fn default() -> Self {
+        ArchRegistry {
+            arches: KNOWN_ARCHES.iter().map(|a| a.to_string()).collect(),
+            groups: ARCH_GROUPS
+                .iter()
+                .map(|(group, archs)| {
+                    (
+                        group.to_string(),
+                        archs.iter().map(|a| a.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ArchRegistry {
+    /// Load from an autobuild4 checkout: every `arch/<arch>.sh` is an
+    /// architecture, every `sets/arch_groups/<group>` lists the members of a group.
+    pub fn from_autobuild4(path: &Path) -> io::Result<Self> {
+        let mut groups = BTreeMap::new();
+        for entry in fs::read_dir(path.join("sets/arch_groups"))? {
+            let entry = entry?;
+            let group = entry.file_name().to_string_lossy().to_lowercase();
+            if BUILD_VARIANTS.contains(&group.as_str()) {
+                continue;
+            }
+            let members = fs::read_to_string(entry.path())?
+                .lines()
+                .map(|l| l.split('#').next().unwrap_or_default())
+                .flat_map(|l| l.split_whitespace())
+                .map(|a| a.to_lowercase())
+                .collect();
+            groups.insert(group, members);
+        }
+
+        let mut arches: Vec<String> = Vec::new();
+        if let Ok(dir) = fs::read_dir(path.join("arch")) {
+            for entry in dir {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                if let Some(arch) = name.strip_suffix(".sh") {
+                    if !arch.starts_with('_') {
+                        arches.push(arch.to_lowercase());
+                    }
+                }
+            }
+        }
+        // Older checkouts have no per-arch scripts, so trust the groups
+        if arches.is_empty() {
+            arches = groups.values().flatten().cloned().collect();
+        }
+        arches.sort();
+        arches.dedup();
+
+        Ok(ArchRegistry { arches, groups })
+    }
+
+    /// The installed autobuild4's arches, or the built-in ones without it. Only read once.
+    pub fn system() -> &'static Self {
+        static SYSTEM: OnceLock<ArchRegistry> = OnceLock::new();
+        SYSTEM.get_or_init(|| Self::from_autobuild4(Path::new(AUTOBUILD4_PATH)).unwrap_or_default())
+    }
+
+    pub fn is_group(&self, name: &str) -> bool {
+        self.groups.contains_key(name)
+    }
+
+    /// Names of the groups `arch` belongs to, in precedence order: the group
+    /// with the most members first, the most specific one last, ties by name.
+    /// An override for a later group wins over one for an earlier group.
+    pub fn groups_of(&self, arch: &str) -> Vec<&str> {
+        let mut res: Vec<(&String, &Vec<String>)> = self
+            .groups
+            .iter()
+            .filter(|(_, members)| members.iter().any(|m| m == arch))
+            .collect();
+        res.sort_by_key(|(group, members)| (Reverse(members.len()), *group));
+        res.into_iter().map(|(group, _)| group.as_str()).collect()
+    }
+}
+
+/// A `FAIL_ARCH` pattern, evaluated the way Bash's extglob would against `$ARCH`.
+/// A word that names an arch group stands for any arch in the group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArchMatcher {
+    pattern: Vec<Glob>,
+    arches: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ExtKind {
+    /// `?(..)`
+    ZeroOrOne,
+    /// `*(..)`
+    ZeroOrMore,
+    /// `+(..)`
+    OneOrMore,
+    /// `@(..)`
+    One,
+    /// `!(..)`
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Glob {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[a-z]`, `[!0-9]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Ext {
+        kind: ExtKind,
+        alternatives: Vec<Vec<Glob>>,
+    },
+}
+
+impl ArchMatcher {
+    pub fn new(pattern: &str, registry: &ArchRegistry) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let mut glob = parse_sequence(&chars, &mut pos, false)?;
+        if pos != chars.len() {
+            return Err(format!("unexpected {} in {}", chars[pos], pattern));
+        }
+        expand_groups(&mut glob, registry);
+
+        Ok(ArchMatcher {
+            pattern: glob,
+            arches: registry.arches.clone(),
+        })
+    }
+
+    /// Whether the pattern matches `arch`, i.e. the package fails to build there.
+    pub fn matches(&self, arch: &str) -> bool {
+        let arch: Vec<char> = arch.to_lowercase().chars().collect();
+        match_sequence(&self.pattern, &arch)
+    }
+
+    /// Known arches the pattern does not match.
+    pub fn supported_arches(&self) -> Vec<&str> {
+        self.arches
+            .iter()
+            .filter(|a| !self.matches(a))
+            .map(|a| a.as_str())
+            .collect()
+    }
+}
+
+fn parse_sequence(chars: &[char], pos: &mut usize, nested: bool) -> Result<Vec<Glob>, String> {
+    let mut res = Vec::new();
+    while let Some(&c) = chars.get(*pos) {
+        let next = chars.get(*pos + 1);
+        match c {
+            '?' | '*' | '+' | '@' | '!' if next == Some(&'(') => {
+                let kind = match c {
+                    '?' => ExtKind::ZeroOrOne,
+                    '*' => ExtKind::ZeroOrMore,
+                    '+' => ExtKind::OneOrMore,
+                    '@' => ExtKind::One,
+                    _ => ExtKind::Not,
+                };
+                *pos += 2;
+                let mut alternatives = vec![parse_sequence(chars, pos, true)?];
+                loop {
+                    match chars.get(*pos) {
+                        Some('|') => {
+                            *pos += 1;
+                            alternatives.push(parse_sequence(chars, pos, true)?);
+                        }
+                        Some(')') => {
+                            *pos += 1;
+                            break;
+                        }
+                        _ => return Err("unbalanced parenthesis".to_string()),
+                    }
+                }
+                res.push(Glob::Ext { kind, alternatives });
+                continue;
+            }
+            '|' | ')' if nested => return Ok(res),
+            '|' | '(' | ')' => return Err(format!("unexpected {} outside of a pattern list", c)),
+            '?' => res.push(Glob::AnyChar),
+            '*' => res.push(Glob::AnyString),
+            '[' => match parse_class(chars, *pos) {
+                Some((class, end)) => {
+                    res.push(class);
+                    *pos = end;
+                    continue;
+                }
+                // Bash takes an unterminated bracket literally
+                None => res.push(Glob::Literal('[')),
+            },
+            '\\' => {
+                *pos += 1;
+                match chars.get(*pos) {
+                    Some(&escaped) => res.push(Glob::Literal(escaped)),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            _ => res.push(Glob::Literal(c)),
+        }
+        *pos += 1;
+    }
+
+    if nested {
+        Err("unbalanced parenthesis".to_string())
+    } else {
+        Ok(res)
+    }
+}
+
+/// Parse `[...]` starting at `start`, returning the class and the position after `]`.
+fn parse_class(chars: &[char], start: usize) -> Option<(Glob, usize)> {
+    let mut pos = start + 1;
+    let negated = matches!(chars.get(pos), Some('!') | Some('^'));
+    if negated {
+        pos += 1;
+    }
+    let mut ranges = Vec::new();
+    // A leading `]` is part of the set
+    let mut first = true;
+    loop {
+        let c = *chars.get(pos)?;
+        if c == ']' && !first {
+            return Some((Glob::Class { negated, ranges }, pos + 1));
+        }
+        first = false;
+        if chars.get(pos + 1) == Some(&'-') && chars.get(pos + 2).is_some_and(|e| *e != ']') {
+            ranges.push((c, chars[pos + 2]));
+            pos += 3;
+        } else {
+            ranges.push((c, c));
+            pos += 1;
+        }
+    }
+}
+
+/// Replace words naming a group with `@(member|...)`.
+fn expand_groups(glob: &mut Vec<Glob>, registry: &ArchRegistry) {
+    let word: Option<String> = glob
+        .iter()
+        .map(|g| match g {
+            Glob::Literal(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    if let Some(members) = word.and_then(|w| registry.groups.get(&w.to_lowercase())) {
+        *glob = vec![Glob::Ext {
+            kind: ExtKind::One,
+            alternatives: members
+                .iter()
+                .map(|m| m.chars().map(Glob::Literal).collect())
+                .collect(),
+        }];
+        return;
+    }
+
+    for g in glob.iter_mut() {
+        if let Glob::Ext { alternatives, .. } = g {
+            for alternative in alternatives.iter_mut() {
+                expand_groups(alternative, registry);
+            }
+        }
+    }
+}
+
+fn match_sequence(glob: &[Glob], s: &[char]) -> bool {
+    let (first, rest) = match glob.split_first() {
+        Some(split) => split,
+        None => return s.is_empty(),
+    };
+    match first {
+        Glob::Literal(c) => s.first() == Some(c) && match_sequence(rest, &s[1..]),
+        Glob::AnyChar => !s.is_empty() && match_sequence(rest, &s[1..]),
+        Glob::AnyString => (0..=s.len()).any(|k| match_sequence(rest, &s[k..])),
+        Glob::Class { negated, ranges } => match s.first() {
+            Some(c) => {
+                ranges.iter().any(|(lo, hi)| lo <= c && c <= hi) != *negated
+                    && match_sequence(rest, &s[1..])
+            }
+            None => false,
+        },
+        Glob::Ext { kind, alternatives } => (0..=s.len()).any(|k| {
+            let head = &s[..k];
+            let any = || alternatives.iter().any(|a| match_sequence(a, head));
+            let head_matches = match kind {
+                ExtKind::ZeroOrOne => head.is_empty() || any(),
+                ExtKind::ZeroOrMore => match_repeated(alternatives, head),
+                ExtKind::OneOrMore => {
+                    any() || (!head.is_empty() && match_repeated(alternatives, head))
+                }
+                ExtKind::One => any(),
+                ExtKind::Not => !any(),
+            };
+            head_matches && match_sequence(rest, &s[k..])
+        }),
+    }
+}
+
+/// Whether `s` is made of zero or more pieces, each matching one of `alternatives`.
+fn match_repeated(alternatives: &[Vec<Glob>], s: &[char]) -> bool {
+    s.is_empty()
+        || (1..=s.len()).any(|k| {
+            alternatives.iter().any(|a| match_sequence(a, &s[..k]))
+                && match_repeated(alternatives, &s[k..])
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fail_arch_patterns() {
+        let registry = ArchRegistry::default();
+        let cases = vec![
+            ("@(ppc64|powerpc)", "powerpc", true),
+            ("@(ppc64|powerpc)", "ppc64el", false),
+            ("!(amd64|arm64)", "amd64", false),
+            ("!(amd64|arm64)", "riscv64", true),
+            ("ppc*", "ppc64el", true),
+            ("ppc*", "powerpc", false),
+            ("!(ppc*)", "powerpc", true),
+            ("loongson[23]", "loongson3", true),
+            ("loongson[!2]", "loongson2f", false),
+            ("+(mips|64|r6el)", "mips64r6el", true),
+            ("retro", "i486", true),
+            ("retro", "amd64", false),
+            ("!(retro|riscv64)", "riscv64", false),
+            ("!(retro|riscv64)", "amd64", true),
+            ("!(mainline)", "m68k", true),
+        ];
+        for (pattern, arch, expected) in cases {
+            let matcher = ArchMatcher::new(pattern, &registry).unwrap();
+            assert_eq!(matcher.matches(arch), expected, "{} {}", pattern, arch);
+        }
+
+        let bad_cases = vec!["ppc64|amd64", "@(amd64", "amd64)", "!(a|(b))"];
+        for case in bad_cases {
+            assert!(ArchMatcher::new(case, &registry).is_err(), "{}", case);
+        }
+
+        let matcher = ArchMatcher::new("!(amd64|arm64)", &registry).unwrap();
+        assert_eq!(matcher.supported_arches(), vec!["amd64", "arm64"]);
+    }
+
+    #[test]
+    fn test_from_autobuild4() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let groups = dir.path().join("sets/arch_groups");
+        fs::create_dir_all(&groups).unwrap();
+        fs::write(groups.join("mainline"), "amd64\narm64 # comment\n").unwrap();
+        fs::write(groups.join("optenv32"), "amd64\narm64\n").unwrap();
+
+        let registry = ArchRegistry::from_autobuild4(dir.path()).unwrap();
+        assert_eq!(registry.arches, vec!["amd64", "arm64"]);
+        assert_eq!(registry.groups.keys().collect::<Vec<_>>(), ["mainline"]);
+        assert_eq!(registry.groups_of("amd64"), vec!["mainline"]);
+    }
+}
@@ -0,0 +1,150 @@
+use super::{ArchPackage, ArchRegistry, Dependency, Package};
+use std::fmt;
+
+/// A deb822 stanza, as in the `control` file of a binary package.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ControlStanza {
+    /// In output order
+    pub fields: Vec<(String, String)>,
+}
+
+impl ControlStanza {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Add a field, unless `value` is empty.
+    pub fn push(&mut self, name: &str, value: &str) {
+        if !value.is_empty() {
+            self.fields.push((name.to_string(), value.to_string()));
+        }
+    }
+}
+
+impl ArchPackage {
+    /// What autobuild would write to the `control` file of this package.
+    /// `Maintainer` and `Installed-Size` are only known at build time and left out.
+    pub fn control(&self) -> ControlStanza {
+        let arch = if self.is_noarch() { "all" } else { &self.arch };
+        let mut res = ControlStanza::default();
+        res.push("Package", &self.name);
+        res.push("Version", &self.full_version());
+        res.push("Architecture", arch);
+        res.push("Section", &self.pkg_section);
+        for (name, deps) in [
+            ("Depends", &self.dependencies),
+            ("Breaks", &self.package_breaks),
+            ("Replaces", &self.package_replaces),
+            ("Provides", &self.package_provides),
+            ("Recommends", &self.recommends),
+            ("Suggests", &self.package_suggests),
+            ("Conflicts", &self.conflicts),
+        ] {
+            res.push(name, &control_list(deps));
+        }
+        res.push("Description", &self.description);
+        res
+    }
+}
+
+impl Package {
+    /// The `control` stanza of this package built for `arch`, or `None` if
+    /// `FAIL_ARCH` rules `arch` out.
+    pub fn control(&self, arch: &str, arches: &ArchRegistry) -> Option<ControlStanza> {
+        let pkg = self.for_arch(arch, arches);
+        if !pkg.available {
+            return None;
+        }
+        Some(pkg.control())
+    }
+}
+
+/// `foo, bar (>= 1.0) | baz`, without repeating an entry.
+fn control_list(deps: &[Dependency]) -> String {
+    let mut res: Vec<String> = Vec::new();
+    for dep in deps.iter().map(|d| d.to_debian()) {
+        if !res.contains(&dep) {
+            res.push(dep);
+        }
+    }
+    res.join(", ")
+}
+
+impl fmt::Display for ControlStanza {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.fields.iter() {
+            let mut lines = value.lines();
+            writeln!(f, "{}: {}", name, lines.next().unwrap_or_default())?;
+            // Continuation lines are indented, with `.` standing for an empty line
+            for line in lines {
+                if line.trim().is_empty() {
+                    writeln!(f, " .")?;
+                } else {
+                    writeln!(f, " {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout::TreeLayout, package::SectionRegistry};
+    use std::{collections::HashMap, path::Path};
+
+    #[test]
+    fn test_control() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGVER", "1.0"),
+            ("PKGREL", "2"),
+            ("PKGEPOCH", "1"),
+            ("PKGDES", "Foo tool"),
+            ("PKGSEC", "admin"),
+            ("PKGDEP", "bar libbaz>=2.0|libqux<3"),
+            ("PKGDEP__AMD64", "bar bar"),
+            ("PKGPROV", "foo-compat==1.0"),
+            ("FAIL_ARCH", "ppc64"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let arches = ArchRegistry::default();
+        let sections = SectionRegistry::default();
+        let spec_path = Path::new("/tree/extra-admin/foo/spec");
+        let layout = TreeLayout::default();
+        let pkg =
+            Package::with_registries(&context, spec_path, &layout, &sections, &arches).unwrap();
+
+        assert_eq!(
+            pkg.control("arm64", &arches).unwrap().to_string(),
+            "Package: foo\n\
+             Version: 1:1.0-2\n\
+             Architecture: arm64\n\
+             Section: admin\n\
+             Depends: bar, libbaz (>= 2.0) | libqux (<< 3)\n\
+             Provides: foo-compat (= 1.0)\n\
+             Description: Foo tool\n"
+        );
+        assert_eq!(
+            pkg.control("amd64", &arches).unwrap().get("depends"),
+            Some("bar")
+        );
+        assert_eq!(pkg.control("ppc64", &arches), None);
+
+        let mut context = context;
+        context.insert("ABHOST".to_string(), "noarch".to_string());
+        let pkg =
+            Package::with_registries(&context, spec_path, &layout, &sections, &arches).unwrap();
+        assert!(pkg.is_noarch());
+        assert_eq!(
+            pkg.control("amd64", &arches).unwrap().get("architecture"),
+            Some("all")
+        );
+    }
+}
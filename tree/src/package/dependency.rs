@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum Relation {
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `==`
+    Eq,
+    /// `>=`
+    Ge,
+    /// `>`
+    Gt,
+}
+
+/// A single package reference, e.g. `autogen<=5.18.12-1` or `gcc:native`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Atom {
+    pub name: String,
+    pub relation: Option<Relation>,
+    pub version: Option<String>,
+    pub arch_qual: Option<String>,
+}
+
+/// One entry of a dependency field: any of `alternatives` will do, e.g. `foo|bar>=1.0`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Dependency {
+    pub alternatives: Vec<Atom>,
+}
+
+// Longest operators first, so that `<=` is not taken as `<`
+const RELATIONS: [(&str, Relation); 5] = [
+    ("<=", Relation::Le),
+    (">=", Relation::Ge),
+    ("==", Relation::Eq),
+    ("<", Relation::Lt),
+    (">", Relation::Gt),
+];
+
+impl Relation {
+    /// As written in defines
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Relation::Lt => "<",
+            Relation::Le => "<=",
+            Relation::Eq => "==",
+            Relation::Ge => ">=",
+            Relation::Gt => ">",
+        }
+    }
+
+    /// As written in a Debian control file
+    pub fn as_debian(&self) -> &'static str {
+        match self {
+            Relation::Lt => "<<",
+            Relation::Le => "<=",
+            Relation::Eq => "=",
+            Relation::Ge => ">=",
+            Relation::Gt => ">>",
+        }
+    }
+
+    /// Whether a version comparing as `ordering` to the one in the atom satisfies it.
+    pub fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            Relation::Lt => ordering == Ordering::Less,
+            Relation::Le => ordering != Ordering::Greater,
+            Relation::Eq => ordering == Ordering::Equal,
+            Relation::Ge => ordering != Ordering::Less,
+            Relation::Gt => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl Atom {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (reference, constraint) = match s.find(['<', '>', '=', '!']) {
+            Some(pos) => (&s[..pos], Some(&s[pos..])),
+            None => (s, None),
+        };
+        let (name, arch_qual) = match reference.split_once(':') {
+            Some((name, qual)) => {
+                if qual.is_empty() || !qual.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(format!("invalid arch qualifier in {}", s));
+                }
+                (name, Some(qual.to_string()))
+            }
+            None => (reference, None),
+        };
+        if !is_valid_name(name) {
+            return Err(format!("invalid package name in {}", s));
+        }
+
+        let (relation, version) = match constraint {
+            Some(constraint) => {
+                let (op, relation) = RELATIONS
+                    .iter()
+                    .find(|(op, _)| constraint.starts_with(op))
+                    .ok_or_else(|| format!("unknown relation in {}", s))?;
+                let version = &constraint[op.len()..];
+                if version.is_empty() {
+                    return Err(format!("missing version in {}", s));
+                }
+                if version.contains(['<', '>', '=', '!']) {
+                    return Err(format!("multiple constraints in {}", s));
+                }
+                if !is_valid_version(version) {
+                    return Err(format!("invalid version in {}", s));
+                }
+                (Some(*relation), Some(version.to_string()))
+            }
+            None => (None, None),
+        };
+
+        Ok(Atom {
+            name: name.to_string(),
+            relation,
+            version,
+            arch_qual,
+        })
+    }
+
+    /// As written in a Debian control file, e.g. `bar (>= 1.0)`
+    pub fn to_debian(&self) -> String {
+        let mut res = self.name.clone();
+        if let Some(arch_qual) = &self.arch_qual {
+            res.push_str(&format!(":{}", arch_qual));
+        }
+        if let (Some(relation), Some(version)) = (&self.relation, &self.version) {
+            res.push_str(&format!(" ({} {})", relation.as_debian(), version));
+        }
+        res
+    }
+}
+
+impl Dependency {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let alternatives = s
+            .split('|')
+            .map(|a| {
+                if a.is_empty() {
+                    Err(format!("empty alternative in {}", s))
+                } else {
+                    Atom::parse(a)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Dependency { alternatives })
+    }
+
+    /// Names of every alternative.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.alternatives.iter().map(|a| a.name.as_str())
+    }
+
+    /// As written in a Debian control file, e.g. `foo | bar (>= 1.0)`
+    pub fn to_debian(&self) -> String {
+        let alternatives: Vec<String> = self.alternatives.iter().map(|a| a.to_debian()).collect();
+        alternatives.join(" | ")
+    }
+}
+
+/// Package names: lower case alphanumerics, `+`, `-` and `.`, starting with an alphanumeric
+fn is_valid_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() || c.is_ascii_digit() => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c))
+}
+
+fn is_valid_version(s: &str) -> bool {
+    s.chars()
+        .all(|c| c.is_ascii_alphanumeric() || ".+-~:".contains(c))
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(arch_qual) = &self.arch_qual {
+            write!(f, ":{}", arch_qual)?;
+        }
+        if let (Some(relation), Some(version)) = (&self.relation, &self.version) {
+            write!(f, "{}{}", relation.as_str(), version)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alternatives: Vec<String> = self.alternatives.iter().map(|a| a.to_string()).collect();
+        write!(f, "{}", alternatives.join("|"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependency_parsing() {
+        assert_eq!(
+            Dependency::parse("autogen<=5.18.12-1"),
+            Ok(Dependency {
+                alternatives: vec![Atom {
+                    name: "autogen".to_string(),
+                    relation: Some(Relation::Le),
+                    version: Some("5.18.12-1".to_string()),
+                    arch_qual: None,
+                }]
+            })
+        );
+
+        let dep = Dependency::parse("gcc:native|clang>1:14").unwrap();
+        assert_eq!(dep.alternatives.len(), 2);
+        assert_eq!(dep.alternatives[0].arch_qual, Some("native".to_string()));
+        assert_eq!(dep.alternatives[1].relation, Some(Relation::Gt));
+        assert_eq!(dep.alternatives[1].version, Some("1:14".to_string()));
+        assert_eq!(dep.to_string(), "gcc:native|clang>1:14");
+
+        let bad_cases = vec![
+            "foo>=",
+            "foo>=1.0<=2.0",
+            "foo=1.0",
+            "foo!=1.0",
+            "foo||bar",
+            "|foo",
+            "Foo",
+            "foo:",
+            ">=1.0",
+        ];
+        for case in bad_cases {
+            assert!(Dependency::parse(case).is_err(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_relation_matches() {
+        use Ordering::*;
+        let cases = [
+            (Relation::Lt, [true, false, false]),
+            (Relation::Le, [true, true, false]),
+            (Relation::Eq, [false, true, false]),
+            (Relation::Ge, [false, true, true]),
+            (Relation::Gt, [false, false, true]),
+        ];
+        for (relation, res) in cases {
+            for (ordering, res) in [Less, Equal, Greater].into_iter().zip(res) {
+                assert_eq!(
+                    relation.matches(ordering),
+                    res,
+                    "{:?} {:?}",
+                    relation,
+                    ordering
+                );
+            }
+        }
+    }
+}
@@ -17,7 +17,14 @@ pub enum PackageErrorType {
     MissingField(String),
     FieldTypeError(String, String),
     FieldSyntaxError(String),
-    InvalidPKGSECError(String),
+    /// (value, closest valid value)
+    InvalidPKGSECError(String, Option<String>),
+    /// (field, entry, reason)
+    DependencySyntaxError(String, String, String),
+    /// (field, value, reason)
+    SourceSyntaxError(String, String, String),
+    /// (version, reason)
+    InvalidVersionError(String, String),
 }
 
 impl fmt::Display for PackageErrorType {
@@ -36,9 +43,25 @@ impl fmt::Display for PackageErrorType {
             PackageErrorType::FieldSyntaxError(field_name) => {
                 write!(f, "Malformed syntax for field {}.", field_name)
             }
-	    PackageErrorType::InvalidPKGSECError(sec) => {
-		write!(f, "Invalid PKGSEC: {}.", sec)
-	    }
+            PackageErrorType::InvalidPKGSECError(sec, None) => {
+                write!(f, "Invalid PKGSEC: {}.", sec)
+            }
+            PackageErrorType::InvalidPKGSECError(sec, Some(suggestion)) => {
+                write!(f, "Invalid PKGSEC: {}, did you mean {}?", sec, suggestion)
+            }
+            PackageErrorType::SourceSyntaxError(field_name, value, reason) => {
+                write!(f, "Malformed field {}={}: {}.", field_name, value, reason)
+            }
+            PackageErrorType::DependencySyntaxError(field_name, entry, reason) => {
+                write!(
+                    f,
+                    "Malformed dependency {} in field {}: {}.",
+                    entry, field_name, reason
+                )
+            }
+            PackageErrorType::InvalidVersionError(version, reason) => {
+                write!(f, "Invalid version {}: {}.", version, reason)
+            }
         }
     }
 }
@@ -1,72 +1,93 @@
+use super::{ArchMatcher, ArchRegistry};
 use serde::{Deserialize, Serialize};
 
+/// `FAIL_ARCH`, kept as written and evaluated against an `ArchRegistry`.
+/// Deserializing evaluates it against `ArchRegistry::system`.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
-pub enum FailArch {
-    Include(Vec<String>),
-    Exclude(Vec<String>),
+#[serde(into = "String", try_from = "String")]
+pub struct FailArch {
+    pub pattern: String,
+    matcher: ArchMatcher,
 }
 
 impl FailArch {
-    pub fn from(s: &str) -> Result<Self, ()> {
-        let chars: Vec<char> = s.chars().collect();
-        if !s.is_empty() && chars[0] == '!' {
-            let archs = get_arch_from_set(&s[1..])?;
-            Ok(FailArch::Exclude(archs))
-        } else {
-            let archs = get_arch_from_set(s)?;
-            Ok(FailArch::Include(archs))
-        }
+    pub fn from(s: &str) -> Result<Self, String> {
+        Self::with_registry(s, ArchRegistry::system())
+    }
+
+    pub fn with_registry(s: &str, arches: &ArchRegistry) -> Result<Self, String> {
+        Ok(FailArch {
+            pattern: s.to_string(),
+            matcher: ArchMatcher::new(&normalize(s), arches)?,
+        })
+    }
+
+    /// Whether building for `arch` is expected to fail.
+    pub fn fails_on(&self, arch: &str) -> bool {
+        self.matcher.matches(arch)
+    }
+
+    /// Known arches the package is expected to build on.
+    pub fn supported_arches(&self) -> Vec<&str> {
+        self.matcher.supported_arches()
     }
-}
 
-/// Example: "(amd64|arm64)"
-fn get_arch_from_set(s: &str) -> Result<Vec<String>, ()> {
-    if s.is_empty() {
-        return Err(());
+    pub fn matcher(&self) -> &ArchMatcher {
+        &self.matcher
     }
-    let chars: Vec<char> = s.chars().collect();
+}
 
-    if chars[0] == '(' && chars[chars.len() - 1] == ')' {
-        let archs_str: String = chars[1..chars.len() - 1].iter().collect();
-        // Split them into individual arches
-        let archs: Vec<String> = archs_str.split('|').map(|s| s.to_string()).collect();
-        Ok(archs)
-    } else if chars
-        .into_iter()
-        .filter(|c| c == &'|' || c == &'(' || c == &')')
-        .count()
-        == 0
-    {
-        let arch = s.to_string();
-        Ok(vec![arch])
+/// Older defines write `(a|b)` for `@(a|b)`.
+fn normalize(s: &str) -> String {
+    if s.starts_with('(') {
+        format!("@{}", s)
     } else {
-        Err(())
+        s.to_string()
+    }
+}
+
+impl From<FailArch> for String {
+    fn from(fail_arch: FailArch) -> Self {
+        fail_arch.pattern
+    }
+}
+
+impl TryFrom<String> for FailArch {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        FailArch::from(&s)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::package::KNOWN_ARCHES;
 
     #[test]
     fn test_set_parsing() {
         let ok_cases = vec![
-            (
-                "(ppc64|powerpc)",
-                vec!["ppc64".to_string(), "powerpc".to_string()],
-            ),
-            ("(ppc64)", vec!["ppc64".to_string()]),
-            ("ppc64", vec!["ppc64".to_string()]),
+            ("(ppc64|powerpc)", vec!["ppc64", "powerpc"]),
+            ("(ppc64)", vec!["ppc64"]),
+            ("ppc64", vec!["ppc64"]),
         ];
 
         let bad_cases = vec!["ppc64|amd64", "ppc64|(amd64|arm64)"];
 
         for (case, res) in ok_cases {
-            assert_eq!(get_arch_from_set(case), Ok(res));
+            let fail_arch = FailArch::with_registry(case, &ArchRegistry::default()).unwrap();
+            let failing: Vec<&str> = KNOWN_ARCHES
+                .into_iter()
+                .filter(|a| fail_arch.fails_on(a))
+                .collect();
+            let mut res = res;
+            res.sort();
+            assert_eq!(failing, res);
         }
 
         for case in bad_cases {
-            assert_eq!(get_arch_from_set(case), Err(()));
+            assert!(FailArch::with_registry(case, &ArchRegistry::default()).is_err());
         }
     }
 }
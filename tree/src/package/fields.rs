@@ -0,0 +1,218 @@
+use super::{PackageError, PackageErrorType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    /// `1`/`0`, `true`/`false`, `yes`/`no`, `on`/`off`
+    Bool,
+    Integer,
+    /// Whitespace-separated words
+    List,
+}
+
+/// A defines field autobuild understands beyond the package metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSpec {
+    /// Variable name, or a prefix ending with `*` such as `AB_FLAGS_*`
+    pub name: &'static str,
+    pub field_type: FieldType,
+    /// What autobuild assumes when the field is not set
+    pub default: Option<&'static str>,
+    /// Whether `NAME__ARCH` overrides are honoured
+    pub per_arch: bool,
+}
+
+const fn field(
+    name: &'static str,
+    field_type: FieldType,
+    default: Option<&'static str>,
+    per_arch: bool,
+) -> FieldSpec {
+    FieldSpec {
+        name,
+        field_type,
+        default,
+        per_arch,
+    }
+}
+
+pub const KNOWN_FIELDS: [FieldSpec; 16] = [
+    field("ABHOST", FieldType::String, None, false),
+    field("ABTYPE", FieldType::String, None, true),
+    field("ABSTRIP", FieldType::Bool, Some("1"), true),
+    field("ABSPLITDBG", FieldType::Bool, Some("1"), true),
+    field("ABELFDEP", FieldType::Bool, Some("0"), true),
+    field("ABSHADOW", FieldType::Bool, Some("1"), true),
+    field("NOLTO", FieldType::Bool, Some("0"), true),
+    field("NOPARALLEL", FieldType::Bool, Some("0"), true),
+    field("NOSTATIC", FieldType::Bool, Some("1"), true),
+    field("AB_FLAGS_*", FieldType::Bool, None, true),
+    field("AUTOTOOLS_AFTER", FieldType::List, None, true),
+    field("CMAKE_AFTER", FieldType::List, None, true),
+    field("MESON_AFTER", FieldType::List, None, true),
+    field("MAKE_AFTER", FieldType::List, None, true),
+    field("CARGO_AFTER", FieldType::List, None, true),
+    field("PKGCONFIG_AFTER", FieldType::List, None, true),
+];
+
+/// Read into dedicated `Package` fields, or belonging to the spec.
+const HANDLED_FIELDS: [&str; 20] = [
+    "PKGNAME",
+    "PKGVER",
+    "PKGREL",
+    "PKGEPOCH",
+    "PKGDES",
+    "PKGSEC",
+    "FAIL_ARCH",
+    "PKGDEP",
+    "BUILDDEP",
+    "PKGSUG",
+    "PKGPROV",
+    "PKGRECOM",
+    "PKGREP",
+    "PKGBREAK",
+    "PKGCONFL",
+    "SRCS",
+    "CHKSUMS",
+    "CHKUPDATE",
+    "SUBDIR",
+    "DUMMYSRC",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum FieldValue {
+    Bool(bool),
+    Integer(i64),
+    List(Vec<String>),
+    String(String),
+}
+
+/// Whether an `ABHOST` value marks a package as arch-independent.
+pub(crate) fn is_noarch(abhost: Option<&FieldValue>) -> bool {
+    matches!(abhost, Some(FieldValue::String(s)) if s == "noarch")
+}
+
+/// Typed and leftover fields of one defines (and the spec it shares).
+#[derive(Debug, Default)]
+pub(crate) struct DefinesFields {
+    /// Known fields keyed as written, e.g. `NOLTO__AMD64`
+    pub options: BTreeMap<String, FieldValue>,
+    /// Translated `PKGDES`, keyed by locale, e.g. `zh_CN` for `PKGDES_zh_CN`
+    pub descriptions: BTreeMap<String, String>,
+    pub extra: BTreeMap<String, String>,
+}
+
+impl FieldSpec {
+    /// The spec for `name`, which may carry an `__ARCH` suffix.
+    pub fn lookup(name: &str) -> Option<&'static FieldSpec> {
+        let (base, arch) = match name.split_once("__") {
+            Some((base, arch)) => (base, Some(arch)),
+            None => (name, None),
+        };
+        KNOWN_FIELDS
+            .iter()
+            .find(|f| match f.name.strip_suffix('*') {
+                Some(prefix) => base.starts_with(prefix) && base.len() > prefix.len(),
+                None => f.name == base,
+            })
+            .filter(|f| arch.is_none() || f.per_arch)
+    }
+
+    pub fn parse(&self, value: &str) -> Option<FieldValue> {
+        Some(match self.field_type {
+            FieldType::String => FieldValue::String(value.to_string()),
+            FieldType::Bool => FieldValue::Bool(match value.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" | "" => false,
+                _ => return None,
+            }),
+            FieldType::Integer => FieldValue::Integer(value.parse().ok()?),
+            FieldType::List => {
+                FieldValue::List(value.split_whitespace().map(|s| s.to_string()).collect())
+            }
+        })
+    }
+
+    pub fn default_value(&self) -> Option<FieldValue> {
+        self.parse(self.default?)
+    }
+}
+
+impl DefinesFields {
+    pub fn from(context: &HashMap<String, String>, pkgname: &str) -> Result<Self, PackageError> {
+        let mut res = DefinesFields::default();
+        for (name, value) in context {
+            let base = name.split_once("__").map(|(b, _)| b).unwrap_or(name);
+            if HANDLED_FIELDS.contains(&base) {
+                continue;
+            }
+            if let Some(spec) = FieldSpec::lookup(name) {
+                let value = spec.parse(value).ok_or_else(|| PackageError {
+                    pkgname: pkgname.to_string(),
+                    error: PackageErrorType::FieldTypeError(
+                        name.to_string(),
+                        format!("{:?}", spec.field_type).to_lowercase(),
+                    ),
+                })?;
+                res.options.insert(name.to_string(), value);
+            } else if let Some(locale) = name.strip_prefix("PKGDES_") {
+                res.descriptions
+                    .insert(locale.to_string(), value.to_string());
+            } else {
+                res.extra.insert(name.to_string(), value.to_string());
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defines_fields() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGDEP__AMD64", "bar"),
+            ("ABHOST", "noarch"),
+            ("NOLTO__AMD64", "yes"),
+            ("AB_FLAGS_O3", "1"),
+            ("CMAKE_AFTER", "-DFOO=ON  -DBAR=OFF"),
+            ("PKGDES_zh_CN", "Foo 工具"),
+            ("ABHOST__AMD64", "noarch"),
+            ("MY_VAR", "1"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let fields = DefinesFields::from(&context, "foo").unwrap();
+
+        assert_eq!(
+            fields.options.keys().collect::<Vec<_>>(),
+            vec!["ABHOST", "AB_FLAGS_O3", "CMAKE_AFTER", "NOLTO__AMD64"]
+        );
+        assert_eq!(fields.options["NOLTO__AMD64"], FieldValue::Bool(true));
+        assert_eq!(
+            fields.options["CMAKE_AFTER"],
+            FieldValue::List(vec!["-DFOO=ON".to_string(), "-DBAR=OFF".to_string()])
+        );
+        assert_eq!(fields.descriptions["zh_CN"], "Foo 工具");
+        // ABHOST cannot be overridden per arch
+        assert_eq!(
+            fields.extra.keys().collect::<Vec<_>>(),
+            vec!["ABHOST__AMD64", "MY_VAR"]
+        );
+
+        let context: HashMap<String, String> = [("NOLTO", "maybe".to_string())]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert!(DefinesFields::from(&context, "foo").is_err());
+    }
+}
@@ -1,17 +1,42 @@
+mod arch;
+mod arch_matcher;
+mod control;
+mod dependency;
 mod error;
 mod fail_arch;
+mod fields;
 mod pkgsec;
+mod source;
+mod source_package;
+mod version;
+pub use arch::ArchPackage;
+pub use arch_matcher::{ArchMatcher, ArchRegistry, ARCH_GROUPS, BUILD_VARIANTS, KNOWN_ARCHES};
+pub use control::ControlStanza;
+pub use dependency::{Atom, Dependency, Relation};
 pub use error::{PackageError, PackageErrorType};
 pub use fail_arch::FailArch;
+pub use fields::{FieldSpec, FieldType, FieldValue, KNOWN_FIELDS};
+pub use pkgsec::{PkgSection, SectionRegistry, AUTOBUILD4_PATH, COMPONENTS, SECTIONS};
+pub use source::{Checksum, ChkUpdate, Source, SourceEntry, SourceOptions};
+pub use source_package::{SourcePackage, SpecSources, SubPackage};
+pub(crate) use version::full_version;
+pub use version::PackageVersion;
 
-use pkgsec::check_pkgsec;
+use crate::layout::TreeLayout;
+use fields::{is_noarch, DefinesFields};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+use version::validate_version;
 
-/// HashMap<String, Vec<(String, Option<String>, Option<String>)>>  ->  HashMap<arch, Vec<(dependency, relop, version)>>
-type PackageDepDependencies = HashMap<String, Vec<(String, Option<String>, Option<String>)>>;
+/// HashMap<arch, Vec<Dependency>>, with `default` for the field without arch suffix
+pub type PackageDepDependencies = HashMap<String, Vec<Dependency>>;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "RawPackage")]
 pub struct Package {
     pub name: String,
     pub epoch: usize,
@@ -21,6 +46,7 @@ pub struct Package {
     pub directory: String,
     pub pkg_section: String,
     pub release: String, // Revision, but in apt's dictionary
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<String>"))]
     pub fail_arch: Option<FailArch>,
     pub description: String,
     pub spec_path: String,
@@ -29,21 +55,110 @@ pub struct Package {
     pub build_dependencies: PackageDepDependencies,
     pub package_suggests: PackageDepDependencies,
     pub package_provides: PackageDepDependencies,
-    pub package_recommands: PackageDepDependencies,
+    pub recommends: PackageDepDependencies,
     pub package_replaces: PackageDepDependencies,
     pub package_breaks: PackageDepDependencies,
-    pub package_configs: PackageDepDependencies,
+    pub conflicts: PackageDepDependencies,
+
+    /// Fields from `KNOWN_FIELDS`, keyed as written, e.g. `NOLTO__AMD64`
+    pub options: BTreeMap<String, FieldValue>,
+    /// Translated `PKGDES`, keyed by locale, e.g. `zh_CN` for `PKGDES_zh_CN`
+    pub descriptions: BTreeMap<String, String>,
+    /// Everything else set in spec or defines
+    pub extra: BTreeMap<String, String>,
+    /// Rebuilt from `epoch`, `version` and `release` on deserialize
+    #[serde(skip)]
+    package_version: PackageVersion,
+}
+
+/// `Package` as serialized, before its version is validated.
+#[derive(Deserialize)]
+struct RawPackage {
+    name: String,
+    epoch: usize,
+    version: String,
+    category: String,
+    section: String,
+    directory: String,
+    pkg_section: String,
+    release: String,
+    fail_arch: Option<FailArch>,
+    description: String,
+    spec_path: String,
+    dependencies: PackageDepDependencies,
+    build_dependencies: PackageDepDependencies,
+    package_suggests: PackageDepDependencies,
+    package_provides: PackageDepDependencies,
+    recommends: PackageDepDependencies,
+    package_replaces: PackageDepDependencies,
+    package_breaks: PackageDepDependencies,
+    conflicts: PackageDepDependencies,
+    options: BTreeMap<String, FieldValue>,
+    descriptions: BTreeMap<String, String>,
+    extra: BTreeMap<String, String>,
+}
+
+impl TryFrom<RawPackage> for Package {
+    type Error = PackageError;
+
+    fn try_from(raw: RawPackage) -> Result<Self, Self::Error> {
+        let package_version = validate_version(&raw.name, raw.epoch, &raw.version, &raw.release)?;
+        Ok(Package {
+            name: raw.name,
+            epoch: raw.epoch,
+            version: raw.version,
+            category: raw.category,
+            section: raw.section,
+            directory: raw.directory,
+            pkg_section: raw.pkg_section,
+            release: raw.release,
+            fail_arch: raw.fail_arch,
+            description: raw.description,
+            spec_path: raw.spec_path,
+            dependencies: raw.dependencies,
+            build_dependencies: raw.build_dependencies,
+            package_suggests: raw.package_suggests,
+            package_provides: raw.package_provides,
+            recommends: raw.recommends,
+            package_replaces: raw.package_replaces,
+            package_breaks: raw.package_breaks,
+            conflicts: raw.conflicts,
+            options: raw.options,
+            descriptions: raw.descriptions,
+            extra: raw.extra,
+            package_version,
+        })
+    }
 }
 
 const NAME_FILED: &str = "PKGNAME";
 // TODO: Add PKGSEC
 const MANDATORY_FIELDS: [&str; 3] = ["PKGVER", "PKGDES", "PKGSEC"];
-const ABBS_CATEGORIES: [&str; 6] = ["app-", "core-", "desktop-", "lang-", "meta-", "runtime-"];
 
 impl Package {
+    /// Parse a package in a tree with the default `TreeLayout`, validating `PKGSEC`
+    /// against `SectionRegistry::system` and reading `FAIL_ARCH` against `ArchRegistry::system`.
     pub fn from(
         context: &HashMap<String, String>,
         spec_path: &Path,
+    ) -> Result<Self, error::PackageError> {
+        Self::with_registries(
+            context,
+            spec_path,
+            &TreeLayout::default(),
+            SectionRegistry::system(),
+            ArchRegistry::system(),
+        )
+    }
+
+    /// Parse a package, taking its category and section from where `layout`
+    /// puts `spec_path`.
+    pub fn with_registries(
+        context: &HashMap<String, String>,
+        spec_path: &Path,
+        layout: &TreeLayout,
+        sections: &SectionRegistry,
+        arches: &ArchRegistry,
     ) -> Result<Self, error::PackageError> {
         let name = match context.get(NAME_FILED) {
             Some(name) => name.to_string(),
@@ -65,52 +180,48 @@ impl Package {
             }
         }
 
-        // extract /tmp/aosc-os-abbs/extra-admin/packagekit/spec -> category: extra  section: admin
-        let spec_path_str = spec_path.to_str().ok_or(PackageError {
-            pkgname: name.clone(),
-            error: PackageErrorType::FieldSyntaxError("CATEGORY".to_string()),
-        })?;
-        let mut category = String::new();
-        let mut section = String::new();
-        for abbs_category in ABBS_CATEGORIES {
-            if let Some(pos1) = spec_path_str.find(abbs_category) {
-                if let Some(pos2) = spec_path_str[pos1..].find('/') {
-                    let category_and_section = &spec_path_str[pos1..][..pos2];
-                    section = category_and_section[abbs_category.len()..].to_string();
-                    category = abbs_category[..abbs_category.len() - 1].to_string();
-                }
-            }
-        }
+        // /tmp/aosc-os-abbs/extra-admin/packagekit/spec -> category: extra  section: admin
+        let (category, section) = match layout.locate_spec(spec_path) {
+            Some(location) => (location.category, location.section),
+            None => (String::new(), String::new()),
+        };
 
-	let pkg_section = check_pkgsec(&name.as_str(),
-		context.get("PKGSEC").unwrap_or(&"".to_string()).to_owned())?;
+        let pkg_section = sections
+            .validate(&name, context.get("PKGSEC").map_or("", |s| s.as_str()))?
+            .to_string();
+        let fields = DefinesFields::from(context, &name)?;
 
         // Get important fields
+        let version = context.get("PKGVER").unwrap().to_string();
+        let epoch = match context.get("PKGEPOCH") {
+            Some(epoch) => match epoch.parse() {
+                Ok(epoch) => epoch,
+                Err(_e) => {
+                    return Err(PackageError {
+                        pkgname: name,
+                        error: PackageErrorType::FieldTypeError(
+                            "PKGEPOCH".to_string(),
+                            "unsigned int".to_string(),
+                        ),
+                    });
+                }
+            },
+            None => 0,
+        };
+        let release = match context.get("PKGREL") {
+            Some(rel) => rel.to_string(),
+            None => "0".to_string(),
+        };
+        let package_version = validate_version(&name, epoch, &version, &release)?;
+
         let res = Package {
             name: context.get("PKGNAME").unwrap().to_string(),
-            version: context.get("PKGVER").unwrap().to_string(),
-            epoch: match context.get("PKGEPOCH") {
-                Some(epoch) => match epoch.parse() {
-                    Ok(epoch) => epoch,
-                    Err(_e) => {
-                        return Err(PackageError {
-                            pkgname: name,
-                            error: PackageErrorType::FieldTypeError(
-                                "PKGEPOCH".to_string(),
-                                "unsigned int".to_string(),
-                            ),
-                        });
-                    }
-                },
-                None => 0,
-            },
-            release: match context.get("PKGREL") {
-                Some(rel) => rel.to_string(),
-                None => "0".to_string(),
-            },
+            version,
+            epoch,
+            release,
             fail_arch: {
                 if let Some(s) = context.get("FAIL_ARCH") {
-                    match FailArch::from(s) {
+                    match FailArch::with_registry(s, arches) {
                         Ok(res) => Some(res),
                         Err(_) => {
                             return Err(PackageError {
@@ -123,14 +234,17 @@ impl Package {
                     None
                 }
             },
-            dependencies: get_field_with_arch_restriction("PKGDEP", context),
-            build_dependencies: get_field_with_arch_restriction("BUILDDEP", context),
-            package_suggests: get_field_with_arch_restriction("PKGSUG", context),
-            package_provides: get_field_with_arch_restriction("PKGPROV", context),
-            package_recommands: get_field_with_arch_restriction("PKGRECOM", context),
-            package_replaces: get_field_with_arch_restriction("PKGREP", context),
-            package_breaks: get_field_with_arch_restriction("PKGBREAK", context),
-            package_configs: get_field_with_arch_restriction("PKGCONFL", context),
+            dependencies: get_field_with_arch_restriction("PKGDEP", context, &name)?,
+            build_dependencies: get_field_with_arch_restriction("BUILDDEP", context, &name)?,
+            package_suggests: get_field_with_arch_restriction("PKGSUG", context, &name)?,
+            package_provides: get_field_with_arch_restriction("PKGPROV", context, &name)?,
+            recommends: get_field_with_arch_restriction("PKGRECOM", context, &name)?,
+            package_replaces: get_field_with_arch_restriction("PKGREP", context, &name)?,
+            package_breaks: get_field_with_arch_restriction("PKGBREAK", context, &name)?,
+            conflicts: get_field_with_arch_restriction("PKGCONFL", context, &name)?,
+            options: fields.options,
+            descriptions: fields.descriptions,
+            extra: fields.extra,
             pkg_section,
             category,
             section,
@@ -140,7 +254,7 @@ impl Package {
                     error: PackageErrorType::FieldSyntaxError("DIRECTORY".to_string()),
                 };
                 let mut spec_path = spec_path.to_path_buf();
-                spec_path.pop().then(|| ()).ok_or_else(|| err.clone())?;
+                spec_path.pop().then_some(()).ok_or_else(|| err.clone())?;
                 let directory = spec_path
                     .file_name()
                     .ok_or_else(|| err.clone())?
@@ -156,65 +270,150 @@ impl Package {
                     error: PackageErrorType::FieldSyntaxError("SPEC_PATH".to_string()),
                 })?
                 .to_string(),
+            package_version,
         };
 
         Ok(res)
     }
+
+    /// The version as the solver compares it, checked when the package was loaded.
+    pub fn package_version(&self) -> &PackageVersion {
+        &self.package_version
+    }
+
+    /// `epoch:version-release` as it would appear in the built package, e.g. `1:2.0-1`
+    pub fn full_version(&self) -> String {
+        full_version(self.epoch, &self.version, &self.release)
+    }
+
+    /// Every dependency-like field, paired with the defines variable it comes from.
+    pub fn dependency_fields(&self) -> [(&'static str, &PackageDepDependencies); 8] {
+        [
+            ("PKGDEP", &self.dependencies),
+            ("BUILDDEP", &self.build_dependencies),
+            ("PKGSUG", &self.package_suggests),
+            ("PKGPROV", &self.package_provides),
+            ("PKGRECOM", &self.recommends),
+            ("PKGREP", &self.package_replaces),
+            ("PKGBREAK", &self.package_breaks),
+            ("PKGCONFL", &self.conflicts),
+        ]
+    }
+
+    /// Whether any dependency field has a `__STAGE2` override for bootstrapping.
+    pub fn has_stage2_overrides(&self) -> bool {
+        self.dependency_fields()
+            .iter()
+            .any(|(_, deps)| deps.contains_key("stage2"))
+    }
+
+    /// Value of a `KNOWN_FIELDS` field on `arch` (or regardless of arch if `None`):
+    /// `NAME__ARCH` wins over `NAME__GROUP` for the groups of `arch` in `arches`,
+    /// in `ArchRegistry::groups_of` precedence, then `NAME`, then the table default.
+    pub fn option(
+        &self,
+        name: &str,
+        arch: Option<&str>,
+        arches: &ArchRegistry,
+    ) -> Option<FieldValue> {
+        let mut candidates = Vec::new();
+        if let Some(arch) = arch {
+            let arch = arch.to_lowercase();
+            candidates.push(format!("{}__{}", name, arch.to_uppercase()));
+            candidates.extend(
+                arches
+                    .groups_of(&arch)
+                    .iter()
+                    .rev()
+                    .map(|g| format!("{}__{}", name, g.to_uppercase())),
+            );
+        }
+        candidates.push(name.to_string());
+
+        candidates
+            .iter()
+            .find_map(|c| self.options.get(c).cloned())
+            .or_else(|| FieldSpec::lookup(name)?.default_value())
+    }
+
+    /// `ABHOST=noarch`
+    pub fn is_noarch(&self) -> bool {
+        is_noarch(self.option("ABHOST", None, ArchRegistry::system()).as_ref())
+    }
+
+    pub fn is_available_on(&self, arch: &str) -> bool {
+        match &self.fail_arch {
+            Some(fail_arch) => !fail_arch.fails_on(arch),
+            None => true,
+        }
+    }
+}
+
+/// Entries that apply on `arch`: `FIELD__ARCH` overrides `FIELD__GROUP` for the
+/// groups of `arch` in `arches`, in `ArchRegistry::groups_of` precedence, which
+/// overrides the plain `FIELD`, each as a whole.
+pub fn deps_for_arch<'a>(
+    deps: &'a PackageDepDependencies,
+    arch: &str,
+    arches: &ArchRegistry,
+) -> &'a [Dependency] {
+    let arch = arch.to_lowercase();
+    deps.get(&arch)
+        .or_else(|| {
+            arches
+                .groups_of(&arch)
+                .iter()
+                .rev()
+                .find_map(|group| deps.get(*group))
+        })
+        .or_else(|| deps.get("default"))
+        .map(|d| d.as_slice())
+        .unwrap_or_default()
 }
 
 fn get_field_with_arch_restriction(
     s: &str,
     context: &HashMap<String, String>,
-) -> PackageDepDependencies {
+    pkgname: &str,
+) -> Result<PackageDepDependencies, PackageError> {
     let mut dep = HashMap::new();
     dep.insert(
         "default".to_string(),
-        get_items_from_bash_string(context.get(s).unwrap_or(&String::new()))
-            .iter()
-            .map(|s| split_by_relop(s))
-            .collect(),
+        parse_dependencies(s, context.get(s).unwrap_or(&String::new()), pkgname)?,
     );
     for (arch, deps) in get_fields_with_prefix(context, &format!("{s}__")) {
         dep.insert(
             arch.to_lowercase(),
-            get_items_from_bash_string(&deps)
-                .iter()
-                .map(|s| split_by_relop(s))
-                .collect(),
+            parse_dependencies(&format!("{s}__{arch}"), &deps, pkgname)?,
         );
     }
 
-    dep
+    Ok(dep)
 }
 
-// autogen<=5.18.12-1 -> (autogen, Some(<=), Some(5.18.12-1))
-fn split_by_relop(s: &str) -> (String, Option<String>, Option<String>) {
-    let f = |relop: &str| {
-        let v: Vec<&str> = s.split(relop).collect();
-        if v.len() == 1 {
-            None
-        } else {
-            Some((
-                v[0].to_string(),
-                Some(relop.to_string()),
-                Some(v[1].to_string()),
-            ))
-        }
-    };
-
-    f("<=")
-        .or_else(|| f(">="))
-        .or_else(|| f("=="))
-        .or_else(|| f("<"))
-        .or_else(|| f(">"))
-        .map_or_else(|| (s.to_string(), None, None), |v| v)
+// "autogen<=5.18.12-1 gcc|clang" -> [autogen (<= 5.18.12-1), gcc | clang]
+fn parse_dependencies(
+    field: &str,
+    s: &str,
+    pkgname: &str,
+) -> Result<Vec<Dependency>, PackageError> {
+    get_items_from_bash_string(s)
+        .iter()
+        .map(|entry| {
+            Dependency::parse(entry).map_err(|reason| PackageError {
+                pkgname: pkgname.to_string(),
+                error: PackageErrorType::DependencySyntaxError(
+                    field.to_string(),
+                    entry.to_string(),
+                    reason,
+                ),
+            })
+        })
+        .collect()
 }
 
 fn get_items_from_bash_string(s: &str) -> Vec<String> {
-    s.split(' ')
-        .map(|s| s.to_string())
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<String>>()
+    s.split_whitespace().map(|s| s.to_string()).collect()
 }
 
 /// Find all entries in the HashMap with name that has the given prefix,
@@ -233,3 +432,44 @@ fn get_fields_with_prefix(h: &HashMap<String, String>, prefix: &str) -> Vec<(Str
 
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let context: HashMap<String, String> = [
+            ("PKGNAME", "foo"),
+            ("PKGVER", "1.0"),
+            ("PKGEPOCH", "1"),
+            ("PKGREL", "2"),
+            ("PKGDES", "Foo"),
+            ("PKGSEC", "admin"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let spec_path = Path::new("/tree/extra-admin/foo/spec");
+        let pkg = Package::with_registries(
+            &context,
+            spec_path,
+            &TreeLayout::default(),
+            &SectionRegistry::default(),
+            &ArchRegistry::default(),
+        )
+        .unwrap();
+
+        let mut json = serde_json::to_value(&pkg).unwrap();
+        assert!(json.get("package_version").is_none());
+        let res: Package = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(res, pkg);
+        assert_eq!(res.package_version(), pkg.package_version());
+
+        json["version"] = "1.0-1".into();
+        assert!(serde_json::from_value::<Package>(json.clone()).is_err());
+        json["version"] = "1.0".into();
+        json["release"] = "two".into();
+        assert!(serde_json::from_value::<Package>(json).is_err());
+    }
+}
@@ -1,217 +1,248 @@
-use std::{fs::read_to_string, path::PathBuf};
-
 use super::{PackageError, PackageErrorType};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt, fs, io, path::Path, sync::OnceLock};
+
+/// Where autobuild4 keeps the list of valid `PKGSEC` values.
+pub const AUTOBUILD4_PATH: &str = "/usr/lib/autobuild4";
 
-/// pkgsec.rs - PKGSEC validation
-
-/// Default set of allowed PKGSEC values.
-/// This set will be used if autobuild4 is not installed on the host machine.
-const DEFAULT_PKGSECS: &[&str] = &[
-	"admin",
-	"Bases",
-	"Cinnamon",
-	"cli-mono",
-	"comm",
-	"cryptocurrency",
-	"Cutefish",
-	"database",
-	"debian-installer",
-	"debug",
-	"devel",
-	"doc",
-	"editors",
-	"electronics",
-	"embedded",
-	"fonts",
-	"games",
-	"gnome",
-	"gnu-r",
-	"gnustep",
-	"graphics",
-	"hamradio",
-	"haskell",
-	"httpd",
-	"interpreters",
-	"java",
-	"kde",
-	"kernel",
-	"libdevel",
-	"libs",
-	"lisp",
-	"localization",
-	"LXDE",
-	"LxQt",
-	"MATE",
-	"mail",
-	"math",
-	"misc",
-	"net",
-	"news",
-	"ocaml",
-	"oldlibs",
-	"otherosfs",
-	"perl",
-	"php",
-	"python",
-	"ruby",
-	"science",
-	"shells",
-	"sound",
-	"tex",
-	"text",
-	"Trinity",
-	"utils",
-	"vcs",
-	"video",
-	"virtual",
-	"web",
-	"x11",
-	"xfce",
-	"zope",
-	"non-free/admin",
-	"non-free/Bases",
-	"non-free/Cinnamon",
-	"non-free/cli-mono",
-	"non-free/comm",
-	"non-free/cryptocurrency",
-	"non-free/Cutefish",
-	"non-free/database",
-	"non-free/debian-installer",
-	"non-free/debug",
-	"non-free/devel",
-	"non-free/doc",
-	"non-free/editors",
-	"non-free/electronics",
-	"non-free/embedded",
-	"non-free/fonts",
-	"non-free/games",
-	"non-free/gnome",
-	"non-free/gnu-r",
-	"non-free/gnustep",
-	"non-free/graphics",
-	"non-free/hamradio",
-	"non-free/haskell",
-	"non-free/httpd",
-	"non-free/interpreters",
-	"non-free/java",
-	"non-free/kde",
-	"non-free/kernel",
-	"non-free/libdevel",
-	"non-free/libs",
-	"non-free/lisp",
-	"non-free/localization",
-	"non-free/LXDE",
-	"non-free/LxQt",
-	"non-free/MATE",
-	"non-free/mail",
-	"non-free/math",
-	"non-free/misc",
-	"non-free/net",
-	"non-free/news",
-	"non-free/ocaml",
-	"non-free/oldlibs",
-	"non-free/otherosfs",
-	"non-free/perl",
-	"non-free/php",
-	"non-free/python",
-	"non-free/ruby",
-	"non-free/science",
-	"non-free/shells",
-	"non-free/sound",
-	"non-free/tex",
-	"non-free/text",
-	"non-free/Trinity",
-	"non-free/utils",
-	"non-free/vcs",
-	"non-free/video",
-	"non-free/virtual",
-	"non-free/web",
-	"non-free/x11",
-	"non-free/xfce",
-	"non-free/zope",
-	"contrib/admin",
-	"contrib/Bases",
-	"contrib/Cinnamon",
-	"contrib/cli-mono",
-	"contrib/comm",
-	"contrib/cryptocurrency",
-	"contrib/Cutefish",
-	"contrib/database",
-	"contrib/debian-installer",
-	"contrib/debug",
-	"contrib/devel",
-	"contrib/doc",
-	"contrib/editors",
-	"contrib/electronics",
-	"contrib/embedded",
-	"contrib/fonts",
-	"contrib/games",
-	"contrib/gnome",
-	"contrib/gnu-r",
-	"contrib/gnustep",
-	"contrib/graphics",
-	"contrib/hamradio",
-	"contrib/haskell",
-	"contrib/httpd",
-	"contrib/interpreters",
-	"contrib/java",
-	"contrib/kde",
-	"contrib/kernel",
-	"contrib/libdevel",
-	"contrib/libs",
-	"contrib/lisp",
-	"contrib/localization",
-	"contrib/LXDE",
-	"contrib/LxQt",
-	"contrib/MATE",
-	"contrib/mail",
-	"contrib/math",
-	"contrib/misc",
-	"contrib/net",
-	"contrib/news",
-	"contrib/ocaml",
-	"contrib/oldlibs",
-	"contrib/otherosfs",
-	"contrib/perl",
-	"contrib/php",
-	"contrib/python",
-	"contrib/ruby",
-	"contrib/science",
-	"contrib/shells",
-	"contrib/sound",
-	"contrib/tex",
-	"contrib/text",
-	"contrib/Trinity",
-	"contrib/utils",
-	"contrib/vcs",
-	"contrib/video",
-	"contrib/virtual",
-	"contrib/web",
-	"contrib/x11",
-	"contrib/xfce",
-	"contrib/zope",
+/// Sections used when no autobuild4 is installed on the host machine.
+pub const SECTIONS: [&str; 61] = [
+    "admin",
+    "Bases",
+    "Cinnamon",
+    "cli-mono",
+    "comm",
+    "cryptocurrency",
+    "Cutefish",
+    "database",
+    "debian-installer",
+    "debug",
+    "devel",
+    "doc",
+    "editors",
+    "electronics",
+    "embedded",
+    "fonts",
+    "games",
+    "gnome",
+    "gnu-r",
+    "gnustep",
+    "graphics",
+    "hamradio",
+    "haskell",
+    "httpd",
+    "interpreters",
+    "java",
+    "kde",
+    "kernel",
+    "libdevel",
+    "libs",
+    "lisp",
+    "localization",
+    "LXDE",
+    "LxQt",
+    "MATE",
+    "mail",
+    "math",
+    "misc",
+    "net",
+    "news",
+    "ocaml",
+    "oldlibs",
+    "otherosfs",
+    "perl",
+    "php",
+    "python",
+    "ruby",
+    "science",
+    "shells",
+    "sound",
+    "tex",
+    "text",
+    "Trinity",
+    "utils",
+    "vcs",
+    "video",
+    "virtual",
+    "web",
+    "x11",
+    "xfce",
+    "zope",
 ];
 
-fn read_section_file() -> Result<Vec<String>, std::io::Error> {
-	let section_file_path = PathBuf::from("/usr/lib/autobuild4/sets/section");
-	let mut section_list = Vec::<String>::new();
-	let section_file_content = read_to_string(section_file_path)?;
-	section_file_content.split_whitespace().for_each(|v| section_list.push(v.trim().to_string()));
-	Ok(section_list)
+/// Components a section can be prefixed with, as in `non-free/admin`.
+pub const COMPONENTS: [&str; 2] = ["non-free", "contrib"];
+
+/// Valid `PKGSEC` values: a section, optionally prefixed with a component.
+/// Every component is assumed to go with every section.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SectionRegistry {
+    pub sections: BTreeSet<String>,
+    pub components: BTreeSet<String>,
+}
+
+/// A `PKGSEC` split into its parts, e.g. `non-free/admin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkgSection {
+    pub component: Option<String>,
+    pub section: String,
+}
+
+impl Default for SectionRegistry {
+    fn default() -> Self {
+        SectionRegistry {
+            sections: SECTIONS.iter().map(|s| s.to_string()).collect(),
+            components: COMPONENTS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
 }
 
-pub fn check_pkgsec(pkgname: &str, pkgsec: String) -> Result<String, PackageError> {
-	let section_list = match read_section_file() {
-		Ok(seclist) => seclist,
-		Err(_) => {
-			DEFAULT_PKGSECS.into_iter().map(|x| x.to_string()).collect::<Vec<String>>()
-		}
-	};
-	if section_list.contains(&pkgsec) {
-		return Ok(pkgsec)
-	}
-	Err(PackageError {
-		pkgname: pkgname.to_string(),
-		error: PackageErrorType::InvalidPKGSECError(pkgsec)
-	})
+impl SectionRegistry {
+    /// Load a whitespace-separated list like `sets/section` of autobuild4.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let mut res = SectionRegistry {
+            sections: BTreeSet::new(),
+            components: BTreeSet::new(),
+        };
+        for entry in fs::read_to_string(path)?.split_whitespace() {
+            match entry.split_once('/') {
+                Some((component, section)) => {
+                    res.components.insert(component.to_string());
+                    res.sections.insert(section.to_string());
+                }
+                None => {
+                    res.sections.insert(entry.to_string());
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Load from an autobuild4 checkout or installation.
+    pub fn from_autobuild4(path: &Path) -> io::Result<Self> {
+        Self::from_file(&path.join("sets/section"))
+    }
+
+    /// The installed autobuild4's list, or the built-in one without it. Only read once.
+    pub fn system() -> &'static Self {
+        static SYSTEM: OnceLock<SectionRegistry> = OnceLock::new();
+        SYSTEM.get_or_init(|| Self::from_autobuild4(Path::new(AUTOBUILD4_PATH)).unwrap_or_default())
+    }
+
+    pub fn parse(&self, pkgsec: &str) -> Option<PkgSection> {
+        let (component, section) = match pkgsec.split_once('/') {
+            Some((component, section)) => (Some(component), section),
+            None => (None, pkgsec),
+        };
+        if !self.sections.contains(section)
+            || component.is_some_and(|c| !self.components.contains(c))
+        {
+            return None;
+        }
+
+        Some(PkgSection {
+            component: component.map(|c| c.to_string()),
+            section: section.to_string(),
+        })
+    }
+
+    pub fn contains(&self, pkgsec: &str) -> bool {
+        self.parse(pkgsec).is_some()
+    }
+
+    /// The closest valid value to an invalid `pkgsec`, if any is close enough.
+    pub fn suggest(&self, pkgsec: &str) -> Option<String> {
+        let (component, section) = match pkgsec.split_once('/') {
+            Some((component, section)) => (Some(component), section),
+            None => (None, pkgsec),
+        };
+        let section = closest(section, &self.sections)?;
+        let res = match component {
+            Some(component) => format!("{}/{}", closest(component, &self.components)?, section),
+            None => section.to_string(),
+        };
+
+        Some(res).filter(|s| s != pkgsec)
+    }
+
+    /// Check `pkgsec` of package `pkgname`.
+    pub fn validate(&self, pkgname: &str, pkgsec: &str) -> Result<PkgSection, PackageError> {
+        self.parse(pkgsec).ok_or_else(|| PackageError {
+            pkgname: pkgname.to_string(),
+            error: PackageErrorType::InvalidPKGSECError(pkgsec.to_string(), self.suggest(pkgsec)),
+        })
+    }
+}
+
+impl fmt::Display for PkgSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.component {
+            Some(component) => write!(f, "{}/{}", component, self.section),
+            None => write!(f, "{}", self.section),
+        }
+    }
+}
+
+/// The candidate with the smallest edit distance to `s`, ignoring case, if
+/// it is within a third of the length of `s`.
+fn closest<'a>(s: &str, candidates: &'a BTreeSet<String>) -> Option<&'a str> {
+    let s = s.to_lowercase();
+    candidates
+        .iter()
+        .map(|c| (edit_distance(&s, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= (s.chars().count() / 3).max(1))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.as_str())
+}
+
+/// Levenshtein distance.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                prev.min(cur).min(row[j]) + 1
+            };
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_registry() {
+        let registry = SectionRegistry::default();
+        assert_eq!(
+            registry.parse("non-free/admin"),
+            Some(PkgSection {
+                component: Some("non-free".to_string()),
+                section: "admin".to_string(),
+            })
+        );
+        assert!(registry.contains("LxQt"));
+        assert!(!registry.contains("lxqt"));
+        assert!(!registry.contains("free/admin"));
+        assert!(!registry.contains("non-free/"));
+
+        assert_eq!(registry.suggest("libdev").as_deref(), Some("libdevel"));
+        assert_eq!(registry.suggest("lxqt").as_deref(), Some("LxQt"));
+        assert_eq!(
+            registry.suggest("nonfree/utils").as_deref(),
+            Some("non-free/utils")
+        );
+        assert_eq!(registry.suggest("something"), None);
+        assert_eq!(registry.suggest("admin"), None);
+    }
 }
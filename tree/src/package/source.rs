@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Options between the source type and the URL, e.g. `commit=tags/v1.0;rename=foo`
+pub type SourceOptions = BTreeMap<String, String>;
+
+/// One entry of `SRCS`, e.g. `git::commit=tags/v1.0::https://example.com/foo.git`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum Source {
+    Tarball { url: String, options: SourceOptions },
+    Git { url: String, options: SourceOptions },
+    Svn { url: String, options: SourceOptions },
+    Hg { url: String, options: SourceOptions },
+    Bzr { url: String, options: SourceOptions },
+    Fossil { url: String, options: SourceOptions },
+    File { url: String, options: SourceOptions },
+}
+
+/// One entry of `CHKSUMS`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum Checksum {
+    Skip,
+    Hash { algorithm: String, value: String },
+}
+
+/// A source paired with the `CHKSUMS` entry at the same position.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct SourceEntry {
+    pub source: Source,
+    pub checksum: Option<Checksum>,
+}
+
+/// `CHKUPDATE`, e.g. `anitya::id=1234` or `github::repo=foo/bar;pattern=v(.+)`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum ChkUpdate {
+    Anitya {
+        id: u64,
+    },
+    Github {
+        repo: String,
+        pattern: Option<String>,
+    },
+    Gitlab {
+        repo: String,
+        instance: Option<String>,
+        pattern: Option<String>,
+    },
+    Html {
+        url: String,
+        pattern: Option<String>,
+    },
+    Other {
+        kind: String,
+        options: SourceOptions,
+    },
+}
+
+impl Source {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (kind, rest) = s
+            .split_once("::")
+            .ok_or_else(|| format!("missing source type in {}", s))?;
+        // Options are optional: `tbl::https://...` vs `tbl::rename=foo::https://...`
+        let (options, url) = match rest.split_once("::") {
+            Some((options, url)) if !options.contains("://") => (parse_options(options)?, url),
+            _ => (SourceOptions::new(), rest),
+        };
+        if url.is_empty() {
+            return Err(format!("missing URL in {}", s));
+        }
+        let url = url.to_string();
+
+        Ok(match kind {
+            "tbl" | "tarball" => Source::Tarball { url, options },
+            "git" => Source::Git { url, options },
+            "svn" => Source::Svn { url, options },
+            "hg" => Source::Hg { url, options },
+            "bzr" => Source::Bzr { url, options },
+            "fossil" => Source::Fossil { url, options },
+            "file" => Source::File { url, options },
+            _ => return Err(format!("unknown source type {}", kind)),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            Source::Tarball { url, .. }
+            | Source::Git { url, .. }
+            | Source::Svn { url, .. }
+            | Source::Hg { url, .. }
+            | Source::Bzr { url, .. }
+            | Source::Fossil { url, .. }
+            | Source::File { url, .. } => url,
+        }
+    }
+
+    pub fn options(&self) -> &SourceOptions {
+        match self {
+            Source::Tarball { options, .. }
+            | Source::Git { options, .. }
+            | Source::Svn { options, .. }
+            | Source::Hg { options, .. }
+            | Source::Bzr { options, .. }
+            | Source::Fossil { options, .. }
+            | Source::File { options, .. } => options,
+        }
+    }
+
+    /// Whether this source is fetched from a version control system.
+    pub fn is_vcs(&self) -> bool {
+        !matches!(self, Source::Tarball { .. } | Source::File { .. })
+    }
+}
+
+impl Checksum {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s == "SKIP" {
+            return Ok(Checksum::Skip);
+        }
+        match s.split_once("::") {
+            Some((algorithm, value)) if !algorithm.is_empty() && !value.is_empty() => {
+                Ok(Checksum::Hash {
+                    algorithm: algorithm.to_string(),
+                    value: value.to_string(),
+                })
+            }
+            _ => Err(format!("malformed checksum {}", s)),
+        }
+    }
+}
+
+impl ChkUpdate {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (kind, options) = s
+            .split_once("::")
+            .ok_or_else(|| format!("missing update checker type in {}", s))?;
+        let mut options = parse_options(options)?;
+        let mut take = |key: &str| {
+            options
+                .remove(key)
+                .ok_or_else(|| format!("{} checker requires {}", kind, key))
+        };
+
+        Ok(match kind {
+            "anitya" => ChkUpdate::Anitya {
+                id: take("id")?
+                    .parse()
+                    .map_err(|_| format!("anitya id is not a number in {}", s))?,
+            },
+            "github" => ChkUpdate::Github {
+                repo: take("repo")?,
+                pattern: take("pattern").ok(),
+            },
+            "gitlab" => ChkUpdate::Gitlab {
+                repo: take("repo")?,
+                instance: take("instance").ok(),
+                pattern: take("pattern").ok(),
+            },
+            "html" => ChkUpdate::Html {
+                url: take("url")?,
+                pattern: take("pattern").ok(),
+            },
+            _ => ChkUpdate::Other {
+                kind: kind.to_string(),
+                options,
+            },
+        })
+    }
+}
+
+/// Pair every `SRCS` entry with the `CHKSUMS` entry at the same position.
+pub fn parse_sources(srcs: &str, chksums: Option<&str>) -> Result<Vec<SourceEntry>, String> {
+    let sources = srcs
+        .split_whitespace()
+        .map(Source::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let checksums = match chksums {
+        Some(chksums) => {
+            let checksums = chksums
+                .split_whitespace()
+                .map(Checksum::parse)
+                .collect::<Result<Vec<_>, _>>()?;
+            if checksums.len() != sources.len() {
+                return Err(format!(
+                    "{} sources but {} checksums",
+                    sources.len(),
+                    checksums.len()
+                ));
+            }
+            checksums.into_iter().map(Some).collect()
+        }
+        None => vec![None; sources.len()],
+    };
+
+    Ok(sources
+        .into_iter()
+        .zip(checksums)
+        .map(|(source, checksum)| SourceEntry { source, checksum })
+        .collect())
+}
+
+/// commit=tags/v1.0;rename=foo -> {commit: tags/v1.0, rename: foo}
+fn parse_options(s: &str) -> Result<SourceOptions, String> {
+    let mut options = SourceOptions::new();
+    for option in s.split(';').filter(|o| !o.is_empty()) {
+        let (key, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("malformed option {}", option))?;
+        options.insert(key.to_string(), value.to_string());
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_parsing() {
+        assert_eq!(
+            Source::parse("tbl::https://example.com/foo-1.0.tar.gz"),
+            Ok(Source::Tarball {
+                url: "https://example.com/foo-1.0.tar.gz".to_string(),
+                options: SourceOptions::new(),
+            })
+        );
+
+        let git =
+            Source::parse("git::commit=tags/v1.0;copy-repo=true::https://example.com/foo").unwrap();
+        assert!(git.is_vcs());
+        assert_eq!(git.url(), "https://example.com/foo");
+        assert_eq!(git.options()["commit"], "tags/v1.0");
+        assert_eq!(git.options()["copy-repo"], "true");
+
+        let file = Source::parse("file::rename=foo.patch::https://example.com/a::b").unwrap();
+        assert_eq!(file.url(), "https://example.com/a::b");
+        assert_eq!(file.options()["rename"], "foo.patch");
+
+        for bad in ["https://example.com", "cvs::https://example.com", "tbl::"] {
+            assert!(Source::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_checksum_matching() {
+        let res = parse_sources(
+            "tbl::https://example.com/a.tar.xz git::commit=HEAD::https://example.com/b",
+            Some("sha256::abcd SKIP"),
+        )
+        .unwrap();
+        assert_eq!(
+            res[0].checksum,
+            Some(Checksum::Hash {
+                algorithm: "sha256".to_string(),
+                value: "abcd".to_string(),
+            })
+        );
+        assert_eq!(res[1].checksum, Some(Checksum::Skip));
+
+        assert!(parse_sources("tbl::https://example.com/a.tar.xz", Some("SKIP SKIP")).is_err());
+        assert!(parse_sources("tbl::https://example.com/a.tar.xz", Some("abcd")).is_err());
+    }
+
+    #[test]
+    fn test_chkupdate_parsing() {
+        assert_eq!(
+            ChkUpdate::parse("anitya::id=1234"),
+            Ok(ChkUpdate::Anitya { id: 1234 })
+        );
+        assert_eq!(
+            ChkUpdate::parse("github::repo=foo/bar;pattern=v(.+)"),
+            Ok(ChkUpdate::Github {
+                repo: "foo/bar".to_string(),
+                pattern: Some("v(.+)".to_string()),
+            })
+        );
+        assert!(ChkUpdate::parse("anitya::id=abc").is_err());
+        assert!(ChkUpdate::parse("html::pattern=foo").is_err());
+    }
+}
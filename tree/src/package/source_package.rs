@@ -0,0 +1,135 @@
+use super::{
+    get_fields_with_prefix,
+    source::{parse_sources, ChkUpdate, SourceEntry},
+    PackageError, PackageErrorType,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+/// HashMap<arch, Vec<SourceEntry>>, with `default` for the plain `SRCS`
+pub type SpecSources = HashMap<String, Vec<SourceEntry>>;
+
+/// A package directory: one `spec` shared by one or more `defines`.
+///
+/// Split packages keep each `defines` in a numbered directory next to the spec
+/// (`01-foo/defines`, `02-bar/defines`), which autobuild builds in order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct SourcePackage {
+    pub directory: String,
+    pub spec_path: String,
+    pub version: String,
+    pub release: String,
+    pub sources: SpecSources,
+    pub subdir: Option<String>,
+    pub chkupdate: Option<ChkUpdate>,
+    pub subpackages: Vec<SubPackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct SubPackage {
+    pub name: String,
+    /// Name of the directory holding `defines`, e.g. `autobuild` or `01-foo`.
+    pub dir: String,
+    /// Build order taken from the numbered prefix, `None` for `autobuild/`.
+    pub index: Option<usize>,
+    pub defines_path: String,
+}
+
+impl SubPackage {
+    pub fn new(name: &str, defines_path: &Path) -> Self {
+        let dir = defines_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let index = dir.split_once('-').and_then(|(i, _)| i.parse().ok());
+
+        SubPackage {
+            name: name.to_string(),
+            dir,
+            index,
+            defines_path: defines_path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+impl SourcePackage {
+    /// Build from the context of a parsed (and decorated) spec. Malformed `SRCS`
+    /// and `CHKUPDATE` fields are left unset and returned as errors.
+    pub fn from(context: &HashMap<String, String>, spec_path: &Path) -> (Self, Vec<PackageError>) {
+        // /tmp/aosc-os-abbs/extra-admin/packagekit/spec -> packagekit
+        let directory = spec_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut errors = Vec::new();
+        let mut err = |field: &str, value: &str, reason: String| {
+            errors.push(PackageError {
+                pkgname: directory.clone(),
+                error: PackageErrorType::SourceSyntaxError(
+                    field.to_string(),
+                    value.to_string(),
+                    reason,
+                ),
+            })
+        };
+
+        let mut sources = HashMap::new();
+        if let Some(srcs) = context.get("SRCS") {
+            match parse_sources(srcs, context.get("CHKSUMS").map(|s| s.as_str())) {
+                Ok(entries) => {
+                    sources.insert("default".to_string(), entries);
+                }
+                Err(reason) => err("SRCS", srcs, reason),
+            }
+        }
+        for (arch, srcs) in get_fields_with_prefix(context, "SRCS__") {
+            let chksums = context.get(&format!("CHKSUMS__{}", arch));
+            match parse_sources(&srcs, chksums.map(|s| s.as_str())) {
+                Ok(entries) => {
+                    sources.insert(arch.to_lowercase(), entries);
+                }
+                Err(reason) => err(&format!("SRCS__{}", arch), &srcs, reason),
+            }
+        }
+
+        let chkupdate = match context.get("CHKUPDATE") {
+            Some(s) => match ChkUpdate::parse(s) {
+                Ok(chkupdate) => Some(chkupdate),
+                Err(reason) => {
+                    err("CHKUPDATE", s, reason);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let res = SourcePackage {
+            spec_path: spec_path.to_string_lossy().to_string(),
+            version: context.get("PKGVER").cloned().unwrap_or_default(),
+            release: context
+                .get("PKGREL")
+                .cloned()
+                .unwrap_or_else(|| "0".to_string()),
+            sources,
+            subdir: context.get("SUBDIR").cloned(),
+            chkupdate,
+            subpackages: Vec::new(),
+            directory,
+        };
+
+        (res, errors)
+    }
+
+    /// Names of the binary packages, in build order.
+    pub fn package_names(&self) -> impl Iterator<Item = &str> {
+        self.subpackages.iter().map(|s| s.name.as_str())
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.subpackages.len() > 1
+    }
+}
@@ -0,0 +1,85 @@
+use super::{PackageError, PackageErrorType};
+pub use solver::PackageVersion;
+
+/// `epoch:version-release` as it would appear in the built package, e.g. `1:2.0-1`.
+/// A zero epoch and a zero release are left out.
+pub(crate) fn full_version(epoch: usize, version: &str, release: &str) -> String {
+    let mut res = String::new();
+    if epoch != 0 {
+        res.push_str(&format!("{}:", epoch));
+    }
+    res.push_str(version);
+    if release != "0" {
+        res.push_str(&format!("-{}", release));
+    }
+    res
+}
+
+/// Check `PKGEPOCH`, `PKGVER` and `PKGREL` of `pkgname` against the dpkg
+/// rules the solver applies, and compose them.
+pub(crate) fn validate_version(
+    pkgname: &str,
+    epoch: usize,
+    version: &str,
+    release: &str,
+) -> Result<PackageVersion, PackageError> {
+    let err = |error| PackageError {
+        pkgname: pkgname.to_string(),
+        error,
+    };
+    if release.parse::<usize>().is_err() {
+        return Err(err(PackageErrorType::FieldTypeError(
+            "PKGREL".to_string(),
+            "unsigned int".to_string(),
+        )));
+    }
+    // Either would be taken for the release or epoch separator
+    if let Some(c) = version.chars().find(|c| *c == '-' || *c == ':') {
+        return Err(err(PackageErrorType::InvalidVersionError(
+            version.to_string(),
+            format!("PKGVER cannot contain {}", c),
+        )));
+    }
+
+    let full_version = format!("{}:{}-{}", epoch, version, release);
+    PackageVersion::from(&full_version).map_err(|e| {
+        err(PackageErrorType::InvalidVersionError(
+            full_version,
+            e.to_string(),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_version() {
+        let ok_cases = vec![
+            (0, "1.2.3", "0", "1.2.3"),
+            (1, "2.0~rc1", "3", "1:2.0~rc1-3"),
+            (0, "20230101+git1a2b3c", "1", "20230101+git1a2b3c-1"),
+        ];
+        for (epoch, version, release, res) in ok_cases {
+            let v = validate_version("foo", epoch, version, release).unwrap();
+            assert_eq!(v, PackageVersion::from(res).unwrap());
+        }
+
+        let bad_cases = vec![
+            (0, "1.0-beta", "0"),
+            (0, "1:1.0", "0"),
+            (0, "", "0"),
+            (0, "1.0_beta", "0"),
+            (0, "1.0", "1a"),
+            (0, "1.0", "-1"),
+        ];
+        for (epoch, version, release) in bad_cases {
+            assert!(
+                validate_version("foo", epoch, version, release).is_err(),
+                "{}",
+                version
+            );
+        }
+    }
+}
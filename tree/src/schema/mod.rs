@@ -0,0 +1,71 @@
+//! Versioned JSON output.
+//!
+//! v2 is how `Package` and `SourcePackage` serialize by themselves, with typed
+//! dependencies and `conflicts`/`recommends`. `schema/tree.v2.schema.json`
+//! describes it; regenerate it with
+//! `cargo run --example json_schema --features json-schema`.
+pub mod v1;
+
+use crate::{
+    package::{Package, SourcePackage},
+    tree::Tree,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaVersion {
+    /// Compatibility layout: `package_recommands`, `package_configs` and
+    /// `(name, relop, version)` tuples
+    V1,
+    #[default]
+    V2,
+}
+
+impl SchemaVersion {
+    /// `1`, `v1`, `2` or `v2`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim_start_matches('v') {
+            "1" => Some(SchemaVersion::V1),
+            "2" => Some(SchemaVersion::V2),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TreeDocument {
+    pub schema_version: u32,
+    pub packages: BTreeMap<String, Package>,
+    /// Keyed by spec path
+    pub sources: BTreeMap<String, SourcePackage>,
+}
+
+impl Tree {
+    /// The tree as a schema v2 document.
+    pub fn to_document(&self) -> TreeDocument {
+        TreeDocument {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            packages: self.iter().map(|p| (p.name.clone(), p.clone())).collect(),
+            sources: self
+                .sources()
+                .map(|s| (s.spec_path.clone(), s.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self, version: SchemaVersion) -> serde_json::Result<serde_json::Value> {
+        match version {
+            SchemaVersion::V1 => serde_json::to_value(v1::TreeV1 {
+                packages: self
+                    .iter()
+                    .map(|p| (p.name.clone(), v1::PackageV1::from(p)))
+                    .collect(),
+            }),
+            SchemaVersion::V2 => serde_json::to_value(self.to_document()),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! The layout `Package` was serialized in before schema v2, kept for
+//! consumers that have not migrated yet.
+
+use crate::package::{Dependency, FailArch, Package, PackageDepDependencies, KNOWN_ARCHES};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// HashMap<arch, Vec<(name, relop, version)>>
+pub type PackageDepDependenciesV1 = HashMap<String, Vec<(String, Option<String>, Option<String>)>>;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum FailArchV1 {
+    Include(Vec<String>),
+    Exclude(Vec<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PackageV1 {
+    pub name: String,
+    pub epoch: usize,
+    pub version: String,
+    pub category: String,
+    pub section: String,
+    pub directory: String,
+    pub pkg_section: String,
+    pub release: String,
+    pub fail_arch: Option<FailArchV1>,
+    pub description: String,
+    pub spec_path: String,
+
+    pub dependencies: PackageDepDependenciesV1,
+    pub build_dependencies: PackageDepDependenciesV1,
+    pub package_suggests: PackageDepDependenciesV1,
+    pub package_provides: PackageDepDependenciesV1,
+    pub package_recommands: PackageDepDependenciesV1,
+    pub package_replaces: PackageDepDependenciesV1,
+    pub package_breaks: PackageDepDependenciesV1,
+    pub package_configs: PackageDepDependenciesV1,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TreeV1 {
+    pub packages: HashMap<String, PackageV1>,
+}
+
+impl From<&Package> for PackageV1 {
+    fn from(pkg: &Package) -> Self {
+        PackageV1 {
+            name: pkg.name.clone(),
+            epoch: pkg.epoch,
+            version: pkg.version.clone(),
+            category: pkg.category.clone(),
+            section: pkg.section.clone(),
+            directory: pkg.directory.clone(),
+            pkg_section: pkg.pkg_section.clone(),
+            release: pkg.release.clone(),
+            fail_arch: pkg.fail_arch.as_ref().map(FailArchV1::from),
+            description: pkg.description.clone(),
+            spec_path: pkg.spec_path.clone(),
+            dependencies: dependencies(&pkg.dependencies),
+            build_dependencies: dependencies(&pkg.build_dependencies),
+            package_suggests: dependencies(&pkg.package_suggests),
+            package_provides: dependencies(&pkg.package_provides),
+            package_recommands: dependencies(&pkg.recommends),
+            package_replaces: dependencies(&pkg.package_replaces),
+            package_breaks: dependencies(&pkg.package_breaks),
+            package_configs: dependencies(&pkg.conflicts),
+        }
+    }
+}
+
+/// v1 only had arch lists, so spell the pattern out against the known arches.
+impl From<&FailArch> for FailArchV1 {
+    fn from(fail_arch: &FailArch) -> Self {
+        if fail_arch.pattern.starts_with('!') {
+            FailArchV1::Exclude(
+                fail_arch
+                    .supported_arches()
+                    .into_iter()
+                    .map(|a| a.to_string())
+                    .collect(),
+            )
+        } else {
+            FailArchV1::Include(
+                KNOWN_ARCHES
+                    .into_iter()
+                    .filter(|a| fail_arch.fails_on(a))
+                    .map(|a| a.to_string())
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn dependencies(deps: &PackageDepDependencies) -> PackageDepDependenciesV1 {
+    deps.iter()
+        .map(|(arch, deps)| (arch.clone(), deps.iter().map(dependency).collect()))
+        .collect()
+}
+
+// v1 did not know about alternatives, and kept `a|b` as the name
+fn dependency(dep: &Dependency) -> (String, Option<String>, Option<String>) {
+    match dep.alternatives.as_slice() {
+        [atom] => {
+            let name = match &atom.arch_qual {
+                Some(qual) => format!("{}:{}", atom.name, qual),
+                None => atom.name.clone(),
+            };
+            (
+                name,
+                atom.relation.map(|r| r.as_str().to_string()),
+                atom.version.clone(),
+            )
+        }
+        _ => (dep.to_string(), None, None),
+    }
+}
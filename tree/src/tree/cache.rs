@@ -0,0 +1,325 @@
+use super::{
+    error::TreeError, groups, load_dir, read_file, report::Recorder, walk, LoadOptions, LoadedDir,
+    Tree, TreeLayout, TreeLoadReport,
+};
+
+use crate::package::{ArchRegistry, FailArch, SectionRegistry};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Bump when the cached types change shape.
+const CACHE_FORMAT: u32 = 3;
+
+/// Parsed package directories from a previous load, keyed by spec path.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct TreeCache {
+    format: u32,
+    crate_version: String,
+    layout: TreeLayout,
+    sections: SectionRegistry,
+    arches: ArchRegistry,
+    dirs: BTreeMap<PathBuf, CachedDir>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDir {
+    /// The spec, then every defines
+    files: Vec<FileStamp>,
+    /// `None` if loading it ran into issues, which are not cached
+    dir: Option<LoadedDir>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct FileStamp {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+    sha256: String,
+}
+
+/// What `Tree::from_cached` found out about package directories, by spec path.
+#[derive(Debug, Clone, Default)]
+pub struct CacheReport {
+    /// Not in the cache
+    pub added: Vec<PathBuf>,
+    /// In the cache, but a spec or defines changed
+    pub changed: Vec<PathBuf>,
+    /// In the cache, but gone from the tree
+    pub removed: Vec<PathBuf>,
+    /// Taken from the cache as is
+    pub unchanged: usize,
+    pub load_report: TreeLoadReport,
+}
+
+impl CacheReport {
+    /// Whether nothing had to be parsed again.
+    pub fn is_fresh(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Tree {
+    /// Load a tree, re-parsing only the package directories that changed since
+    /// the cache at `cache_path` was written, then update the cache.
+    pub fn from_cached(path: &Path, cache_path: &Path) -> Result<(Self, CacheReport), TreeError> {
+        Self::load_cached(path, cache_path, &LoadOptions::default())
+    }
+
+    pub fn load_cached(
+        path: &Path,
+        cache_path: &Path,
+        options: &LoadOptions,
+    ) -> Result<(Self, CacheReport), TreeError> {
+        let mut cache = TreeCache::read(cache_path, options);
+        let mut recorder = Recorder::new(options.policy);
+        let pkg_dirs = walk(path, &options.layout, &mut recorder)?;
+
+        let mut report = CacheReport::default();
+        let mut res = Tree::empty(path, options);
+        let mut defined_at: HashMap<String, PathBuf> = HashMap::new();
+        let mut dirs = BTreeMap::new();
+        for (spec_path, defines_paths) in pkg_dirs {
+            let mut paths = vec![spec_path.clone()];
+            paths.extend(defines_paths.iter().cloned());
+
+            let cached = cache.dirs.remove(&spec_path);
+            let stamps = match &cached {
+                Some(cached) => cached.restamp(&paths)?,
+                None => None,
+            };
+            match (&cached, &stamps) {
+                (None, _) => report.added.push(spec_path.clone()),
+                (Some(_), None) => report.changed.push(spec_path.clone()),
+                // Either taken as is, or parsed again to report its issues
+                (Some(_), Some(_)) => (),
+            }
+            let reusable = match cached {
+                Some(CachedDir { dir: Some(dir), .. }) if stamps.is_some() => Some(dir),
+                _ => None,
+            };
+            let files = match stamps {
+                Some(files) => files,
+                None => paths
+                    .iter()
+                    .map(|p| FileStamp::of(p))
+                    .collect::<Result<_, _>>()?,
+            };
+            let (dir, clean) = match reusable {
+                Some(mut dir) => {
+                    report.unchanged += 1;
+                    rematch_fail_arch(&mut dir, &options.arches);
+                    (Some(dir), true)
+                }
+                None => {
+                    let issues = recorder.report.issues.len();
+                    let dir = load_dir(
+                        options,
+                        spec_path.clone(),
+                        defines_paths,
+                        &read_file,
+                        &mut recorder,
+                    )?;
+                    (dir, recorder.report.issues.len() == issues)
+                }
+            };
+            // Whatever loaded joins the tree, but only a clean load is cached
+            let cached_dir = dir.clone().filter(|_| clean);
+            if let Some(dir) = dir {
+                res.insert_dir(dir, &mut defined_at, &mut recorder)?;
+            }
+            dirs.insert(
+                spec_path,
+                CachedDir {
+                    files,
+                    dir: cached_dir,
+                },
+            );
+        }
+        report.removed = cache.dirs.into_keys().collect();
+        res.groups = groups::load_groups(path, &mut recorder)?;
+        report.load_report = recorder.report;
+
+        TreeCache {
+            format: CACHE_FORMAT,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            layout: options.layout.clone(),
+            sections: options.sections.clone(),
+            arches: options.arches.clone(),
+            dirs,
+        }
+        .write(cache_path)?;
+
+        Ok((res, report))
+    }
+}
+
+/// `FAIL_ARCH` comes back from the cache evaluated against `ArchRegistry::system`.
+fn rematch_fail_arch(dir: &mut LoadedDir, arches: &ArchRegistry) {
+    if arches == ArchRegistry::system() {
+        return;
+    }
+    for (pkg, _) in dir.packages.iter_mut() {
+        if let Some(fail_arch) = &pkg.fail_arch {
+            if let Ok(rematched) = FailArch::with_registry(&fail_arch.pattern, arches) {
+                pkg.fail_arch = Some(rematched);
+            }
+        }
+    }
+}
+
+impl TreeCache {
+    /// A missing, unreadable or outdated cache is as good as an empty one.
+    fn read(path: &Path, options: &LoadOptions) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<TreeCache>(&b).ok())
+            .filter(|c| {
+                c.format == CACHE_FORMAT
+                    && c.crate_version == env!("CARGO_PKG_VERSION")
+                    && c.layout == options.layout
+                    && c.sections == options.sections
+                    && c.arches == options.arches
+            })
+            .unwrap_or_default()
+    }
+
+    fn write(&self, path: &Path) -> Result<(), TreeError> {
+        let json = serde_json::to_vec(self).map_err(|e| TreeError::FsError(e.to_string()))?;
+        // Write aside and rename, so that a crash never leaves half a cache
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+impl CachedDir {
+    /// Current stamps of `paths` if they have the cached content, `None` otherwise.
+    /// Files whose mtime and size did not change are not read.
+    fn restamp(&self, paths: &[PathBuf]) -> Result<Option<Vec<FileStamp>>, TreeError> {
+        if self.files.len() != paths.len() {
+            return Ok(None);
+        }
+        let mut res = Vec::new();
+        for (cached, path) in self.files.iter().zip(paths) {
+            if &cached.path != path {
+                return Ok(None);
+            }
+            let metadata = fs::metadata(path)?;
+            if cached.modified.is_some()
+                && cached.modified == metadata.modified().ok()
+                && cached.len == metadata.len()
+            {
+                res.push(cached.clone());
+                continue;
+            }
+            let stamp = FileStamp::of(path)?;
+            if stamp.sha256 != cached.sha256 {
+                return Ok(None);
+            }
+            res.push(stamp);
+        }
+
+        Ok(Some(res))
+    }
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<Self, TreeError> {
+        let metadata = fs::metadata(path)?;
+        let content = fs::read(path)?;
+
+        Ok(FileStamp {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            sha256: format!("{:x}", Sha256::digest(&content)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{package::PackageVersion, tree::fixture::Fixture};
+
+    fn load(fixture: &Fixture) -> (Tree, CacheReport) {
+        let cache_path = fixture.path("cache.json");
+        Tree::load_cached(fixture.root(), &cache_path, &Fixture::options()).unwrap()
+    }
+
+    /// Same packages and directories as a tree loaded from scratch.
+    fn assert_loads_like_uncached(fixture: &Fixture, tree: &Tree) {
+        let (uncached, _) = Tree::load(fixture.root(), &Fixture::options()).unwrap();
+        assert_eq!(
+            serde_json::to_value(tree).unwrap(),
+            serde_json::to_value(&uncached).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_load_cached() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .write("core-libs/bar/spec", "VER=2.0\n")
+            .write(
+                "core-libs/bar/01-bar/defines",
+                "PKGNAME=bar\nPKGSEC=libs\nPKGDES=\"Bar\"\n",
+            )
+            .write(
+                "core-libs/bar/02-libbar/defines",
+                "PKGNAME=libbar\nPKGSEC=libs\nPKGDES=\"libbar\"\n",
+            );
+        let bar_spec = fixture.path("core-libs/bar/spec");
+
+        let (tree, report) = load(&fixture);
+        assert_eq!(report.added.len(), 2);
+        assert_loads_like_uncached(&fixture, &tree);
+        let (tree, report) = load(&fixture);
+        assert!(report.is_fresh());
+        assert_eq!(report.unchanged, 2);
+        assert_loads_like_uncached(&fixture, &tree);
+        assert_eq!(
+            tree.get("bar").unwrap().package_version(),
+            &PackageVersion::from("2.0").unwrap()
+        );
+
+        // One broken sub-package leaves the other one in the tree
+        fixture.write(
+            "core-libs/bar/02-libbar/defines",
+            "PKGNAME=libbar\nPKGDES=\"libbar\"\n",
+        );
+        let (tree, report) = load(&fixture);
+        assert_eq!(report.changed, vec![bar_spec.clone()]);
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.load_report.issues.len(), 1);
+        assert!(tree.get("bar").is_some());
+        assert!(tree.get("libbar").is_none());
+        assert_loads_like_uncached(&fixture, &tree);
+
+        // Not cached, so parsed and reported again
+        let (tree, report) = load(&fixture);
+        assert!(report.changed.is_empty());
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.load_report.issues.len(), 1);
+        assert_loads_like_uncached(&fixture, &tree);
+
+        fixture.write(
+            "core-libs/bar/02-libbar/defines",
+            "PKGNAME=libbar\nPKGSEC=libs\nPKGDES=\"libbar, fixed\"\n",
+        );
+        let (tree, report) = load(&fixture);
+        assert_eq!(report.changed, vec![bar_spec]);
+        assert!(report.load_report.issues.is_empty());
+        assert_eq!(tree.get("libbar").unwrap().description, "libbar, fixed");
+        assert_loads_like_uncached(&fixture, &tree);
+    }
+}
@@ -0,0 +1,219 @@
+use super::Tree;
+use crate::package::{Atom, Dependency, Package};
+
+use serde::{Deserialize, Serialize};
+use solver::PackageVersion;
+use std::fmt;
+
+/// Fields that pull packages in, and so must point at something in the tree.
+pub const REQUIRING_FIELDS: [&str; 4] = ["PKGDEP", "BUILDDEP", "PKGRECOM", "PKGSUG"];
+
+/// A dependency entry that does not make sense against the rest of the tree.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum DependencyIssue {
+    /// No package has this name and no package provides it.
+    Missing { dependency: DependencyRef },
+    /// The version constraint cannot be parsed.
+    MalformedConstraint { dependency: DependencyRef },
+    /// No packaged version satisfies the version constraint.
+    Unsatisfiable {
+        dependency: DependencyRef,
+        /// Versions of everything named or provided as the dependency
+        available: Vec<String>,
+    },
+    /// The dependency is declared for an arch its target fails to build on.
+    FailArch { dependency: DependencyRef },
+}
+
+/// Where a dependency entry was found.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DependencyRef {
+    pub package: String,
+    /// Name of the defines variable, e.g. `PKGDEP`
+    pub field: String,
+    /// Lower-cased arch suffix, or `default`
+    pub arch: String,
+    pub dependency: Dependency,
+}
+
+/// Why one alternative of a dependency cannot be used.
+enum AtomProblem {
+    Missing,
+    MalformedConstraint,
+    Unsatisfiable(Vec<String>),
+    FailArch,
+}
+
+impl Tree {
+    /// Check the dependencies declared in `fields` (e.g. `REQUIRING_FIELDS`) of every package.
+    pub fn check_dependencies(&self, fields: &[&str]) -> Vec<DependencyIssue> {
+        let mut res = Vec::new();
+        let mut names: Vec<&String> = self.packages.keys().collect();
+        names.sort();
+
+        for pkg in names.into_iter().map(|n| &self.packages[n]) {
+            for (field, deps) in pkg.dependency_fields() {
+                if !fields.contains(&field) {
+                    continue;
+                }
+                let mut archs: Vec<&String> = deps.keys().collect();
+                archs.sort();
+                for arch in archs {
+                    for dependency in deps[arch].iter() {
+                        let dependency = DependencyRef {
+                            package: pkg.name.clone(),
+                            field: field.to_string(),
+                            arch: arch.clone(),
+                            dependency: dependency.clone(),
+                        };
+                        if let Some(issue) = self.check_dependency(dependency) {
+                            res.push(issue);
+                        }
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// A dependency is fine as long as one of its alternatives is; otherwise
+    /// report what is wrong with the first one.
+    fn check_dependency(&self, dependency: DependencyRef) -> Option<DependencyIssue> {
+        let mut problems = Vec::new();
+        for atom in dependency.dependency.alternatives.iter() {
+            match self.check_atom(atom, &dependency.arch) {
+                Some(problem) => problems.push(problem),
+                None => return None,
+            }
+        }
+
+        Some(match problems.into_iter().next()? {
+            AtomProblem::Missing => DependencyIssue::Missing { dependency },
+            AtomProblem::MalformedConstraint => DependencyIssue::MalformedConstraint { dependency },
+            AtomProblem::Unsatisfiable(available) => DependencyIssue::Unsatisfiable {
+                dependency,
+                available,
+            },
+            AtomProblem::FailArch => DependencyIssue::FailArch { dependency },
+        })
+    }
+
+    fn check_atom(&self, atom: &Atom, arch: &str) -> Option<AtomProblem> {
+        let target = self.packages.get(&atom.name);
+        let providers: Vec<&Package> = self.providers_of(&atom.name).collect();
+        if target.is_none() && providers.is_empty() {
+            return Some(AtomProblem::Missing);
+        }
+
+        if let Some(target) = target {
+            if arch != "default" && !self.arches.is_group(arch) && !target.is_available_on(arch) {
+                return Some(AtomProblem::FailArch);
+            }
+        }
+
+        if let (Some(relation), Some(version)) = (&atom.relation, &atom.version) {
+            let required = match PackageVersion::from(version) {
+                Ok(required) => required,
+                Err(_) => return Some(AtomProblem::MalformedConstraint),
+            };
+
+            let mut available = Vec::new();
+            if let Some(target) = target {
+                available.push(target.full_version());
+            }
+            // Only versioned provides can satisfy a versioned dependency
+            for provider in providers {
+                for provided in provider
+                    .package_provides
+                    .values()
+                    .flatten()
+                    .flat_map(|d| d.alternatives.iter())
+                {
+                    if provided.name == atom.name {
+                        if let Some(v) = &provided.version {
+                            available.push(v.clone());
+                        }
+                    }
+                }
+            }
+
+            let satisfied = available.iter().any(|v| match PackageVersion::from(v) {
+                Ok(v) => relation.matches(v.cmp(&required)),
+                // Broken versions are not the dependent's fault
+                Err(_) => true,
+            });
+            if !satisfied {
+                return Some(AtomProblem::Unsatisfiable(available));
+            }
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for DependencyRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.package, self.field)?;
+        if self.arch != "default" {
+            write!(f, "__{}", self.arch.to_uppercase())?;
+        }
+        write!(f, " {}", self.dependency)
+    }
+}
+
+impl fmt::Display for DependencyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencyIssue::Missing { dependency } => {
+                write!(f, "{}: no such package or provider", dependency)
+            }
+            DependencyIssue::MalformedConstraint { dependency } => {
+                write!(f, "{}: malformed version constraint", dependency)
+            }
+            DependencyIssue::Unsatisfiable {
+                dependency,
+                available,
+            } => write!(
+                f,
+                "{}: not satisfied by any of [{}]",
+                dependency,
+                available.join(", ")
+            ),
+            DependencyIssue::FailArch { dependency } => {
+                write!(f, "{}: not available on {}", dependency, dependency.arch)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    #[test]
+    fn test_check_dependencies() {
+        let fixture = Fixture::new();
+        fixture
+            .package(
+                "extra-admin/foo",
+                "1.0",
+                "PKGDEP=\"missing bar>=2.0 bar>=1.0\"\nPKGDEP__PPC64=\"baz\"\nPKGRECOM=\"missing|bar\"",
+            )
+            .package("extra-admin/bar", "1.0", "")
+            .package("extra-admin/baz", "1.0", "FAIL_ARCH=\"ppc64\"");
+        let tree = fixture.load();
+
+        let issues = tree.check_dependencies(&REQUIRING_FIELDS);
+        let issues: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "foo: PKGDEP missing: no such package or provider",
+                "foo: PKGDEP bar>=2.0: not satisfied by any of [1.0]",
+                "foo: PKGDEP__PPC64 baz: not available on ppc64",
+            ]
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use super::{revdeps::dependency_names, DependencyKind, Tree};
+
+use abbs_meta_scc::tarjan_search;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+/// Stop looking for minimum feedback edge sets after checking this many candidates.
+const EXACT_SEARCH_BUDGET: usize = 100_000;
+/// Report at most this many alternative minimum feedback edge sets per cycle.
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Every dependency cycle in the tree on one arch.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CycleReport {
+    pub arch: Option<String>,
+    pub cycles: Vec<DependencyCycle>,
+}
+
+/// A strongly connected component with more than one package, or a package
+/// depending on itself.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    pub packages: Vec<String>,
+    pub edges: Vec<CycleEdge>,
+    /// Sets of edges whose removal breaks every cycle, smallest first
+    pub break_points: Vec<Vec<CycleEdge>>,
+    /// Whether `break_points` are guaranteed to be minimum, or just a heuristic
+    pub exact: bool,
+    /// Packages with `__STAGE2` overrides, which can be built without some dependencies
+    pub bootstrappable: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CycleEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+}
+
+impl Tree {
+    /// Find every dependency cycle through `kinds` on `arch`, or on any arch if `None`.
+    pub fn dependency_cycles(&self, arch: Option<&str>, kinds: &[DependencyKind]) -> CycleReport {
+        let packages: BTreeSet<&str> = self
+            .packages
+            .values()
+            .filter(|p| arch.is_none_or(|a| p.is_available_on(a)))
+            .map(|p| p.name.as_str())
+            .collect();
+        let graph = self.dependency_graph(&packages, arch, kinds);
+        let roots: Vec<String> = packages.iter().map(|n| n.to_string()).collect();
+        let get_deps = |n: &str| graph.get(n).cloned().unwrap_or_default();
+        let sccs = tarjan_search(&roots, &get_deps);
+
+        let providers = self.provider_index();
+        let mut cycles = Vec::new();
+        for scc in sccs {
+            // Most packages are on no cycle at all
+            if let [name] = scc.as_slice() {
+                if !graph.get(name).is_some_and(|deps| deps.contains(name)) {
+                    continue;
+                }
+            }
+            let members: BTreeSet<&str> = scc.iter().map(|n| n.as_str()).collect();
+            let edges = self.edges_within(&members, &providers, arch, kinds);
+
+            let bootstrappable: Vec<String> = members
+                .iter()
+                .filter(|n| self.packages[**n].has_stage2_overrides())
+                .map(|n| n.to_string())
+                .collect();
+            let (break_points, exact) = feedback_edge_sets(&members, &edges, &bootstrappable);
+            cycles.push(DependencyCycle {
+                packages: members.iter().map(|n| n.to_string()).collect(),
+                edges,
+                break_points,
+                exact,
+                bootstrappable,
+            });
+        }
+        cycles.sort_by(|a, b| a.packages.cmp(&b.packages));
+
+        CycleReport {
+            arch: arch.map(|a| a.to_string()),
+            cycles,
+        }
+    }
+
+    fn edges_within(
+        &self,
+        members: &BTreeSet<&str>,
+        providers: &HashMap<&str, Vec<&str>>,
+        arch: Option<&str>,
+        kinds: &[DependencyKind],
+    ) -> Vec<CycleEdge> {
+        let mut res = BTreeSet::new();
+        for from in members.iter() {
+            let pkg = &self.packages[*from];
+            for (kind, field) in [
+                (DependencyKind::Runtime, &pkg.dependencies),
+                (DependencyKind::Build, &pkg.build_dependencies),
+            ] {
+                if !kinds.contains(&kind) {
+                    continue;
+                }
+                for dep in dependency_names(field, arch, &self.arches) {
+                    let mut targets = vec![dep];
+                    targets.extend(providers.get(dep).into_iter().flatten());
+                    for to in targets.into_iter().filter(|t| members.contains(t)) {
+                        res.insert(CycleEdge {
+                            from: from.to_string(),
+                            to: to.to_string(),
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+
+        res.into_iter().collect()
+    }
+}
+
+/// Find the smallest sets of edges whose removal makes the component acyclic.
+///
+/// Tries every combination of edges in increasing size while that is cheap
+/// enough, otherwise falls back to the Eades-Lin-Smyth heuristic. Sets cutting
+/// edges out of bootstrappable packages come first.
+fn feedback_edge_sets(
+    members: &BTreeSet<&str>,
+    edges: &[CycleEdge],
+    bootstrappable: &[String],
+) -> (Vec<Vec<CycleEdge>>, bool) {
+    let score = |set: &Vec<CycleEdge>| {
+        set.iter()
+            .filter(|e| !bootstrappable.contains(&e.from))
+            .count()
+    };
+
+    let mut budget = EXACT_SEARCH_BUDGET;
+    for size in 1..=edges.len() {
+        let mut found = Vec::new();
+        let mut chosen = Vec::new();
+        if !search_combinations(
+            members,
+            edges,
+            size,
+            0,
+            &mut chosen,
+            &mut found,
+            &mut budget,
+        ) {
+            break;
+        }
+        if !found.is_empty() {
+            found.sort_by_key(|s| score(s));
+            found.truncate(MAX_SUGGESTIONS);
+            return (found, true);
+        }
+    }
+
+    (vec![greedy_feedback_edges(members, edges)], false)
+}
+
+/// Collect every `size`-edge removal that breaks all cycles. Returns false
+/// once the budget runs out.
+fn search_combinations(
+    members: &BTreeSet<&str>,
+    edges: &[CycleEdge],
+    size: usize,
+    start: usize,
+    chosen: &mut Vec<usize>,
+    found: &mut Vec<Vec<CycleEdge>>,
+    budget: &mut usize,
+) -> bool {
+    if chosen.len() == size {
+        if *budget == 0 {
+            return false;
+        }
+        *budget -= 1;
+        let removed: HashSet<usize> = chosen.iter().copied().collect();
+        let remaining: Vec<&CycleEdge> = edges
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, e)| e)
+            .collect();
+        if is_acyclic(members, &remaining) {
+            found.push(chosen.iter().map(|i| edges[*i].clone()).collect());
+        }
+        return true;
+    }
+
+    for i in start..edges.len() {
+        chosen.push(i);
+        let ok = search_combinations(members, edges, size, i + 1, chosen, found, budget);
+        chosen.pop();
+        if !ok {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Order vertices so that most edges point forward, then cut the ones
+/// pointing backward, and finally put back every cut edge that is not needed.
+fn greedy_feedback_edges(members: &BTreeSet<&str>, edges: &[CycleEdge]) -> Vec<CycleEdge> {
+    let mut left: BTreeSet<&str> = members.clone();
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    let degree = |v: &str, left: &BTreeSet<&str>, outgoing: bool| {
+        edges
+            .iter()
+            .filter(|e| e.from != e.to)
+            .filter(|e| {
+                if outgoing {
+                    e.from == v && left.contains(e.to.as_str())
+                } else {
+                    e.to == v && left.contains(e.from.as_str())
+                }
+            })
+            .count()
+    };
+
+    while !left.is_empty() {
+        if let Some(sink) = left.iter().copied().find(|v| degree(v, &left, true) == 0) {
+            tail.push(sink);
+            left.remove(sink);
+        } else if let Some(source) = left.iter().copied().find(|v| degree(v, &left, false) == 0) {
+            head.push(source);
+            left.remove(source);
+        } else {
+            let best = left
+                .iter()
+                .copied()
+                .max_by_key(|v| degree(v, &left, true) as isize - degree(v, &left, false) as isize)
+                .unwrap();
+            head.push(best);
+            left.remove(best);
+        }
+    }
+    tail.reverse();
+    head.extend(tail);
+    let position: HashMap<&str, usize> = head.iter().enumerate().map(|(i, v)| (*v, i)).collect();
+
+    let mut cut: Vec<CycleEdge> = edges
+        .iter()
+        .filter(|e| position[e.from.as_str()] >= position[e.to.as_str()])
+        .cloned()
+        .collect();
+    let mut i = 0;
+    while i < cut.len() {
+        let candidate = cut.remove(i);
+        let remaining: Vec<&CycleEdge> = edges.iter().filter(|e| !cut.contains(e)).collect();
+        if !is_acyclic(members, &remaining) {
+            cut.insert(i, candidate);
+            i += 1;
+        }
+    }
+
+    cut
+}
+
+fn is_acyclic(members: &BTreeSet<&str>, edges: &[&CycleEdge]) -> bool {
+    let mut indegree: HashMap<&str, usize> = members.iter().map(|m| (*m, 0)).collect();
+    for e in edges.iter() {
+        *indegree.get_mut(e.to.as_str()).unwrap() += 1;
+    }
+    let mut queue: Vec<&str> = indegree
+        .iter()
+        .filter(|(_, d)| **d == 0)
+        .map(|(v, _)| *v)
+        .collect();
+    let mut visited = 0;
+    while let Some(v) = queue.pop() {
+        visited += 1;
+        for e in edges.iter().filter(|e| e.from == v) {
+            let d = indegree.get_mut(e.to.as_str()).unwrap();
+            *d -= 1;
+            if *d == 0 {
+                queue.push(&e.to);
+            }
+        }
+    }
+
+    visited == members.len()
+}
+
+impl CycleReport {
+    /// Render as a Graphviz digraph, one cluster per cycle. Suggested break
+    /// points are dashed, bootstrappable packages are drawn as boxes.
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph cycles {\n");
+        for (i, cycle) in self.cycles.iter().enumerate() {
+            res.push_str(&format!("  subgraph cluster_{} {{\n", i));
+            for pkg in cycle.packages.iter() {
+                let shape = if cycle.bootstrappable.contains(pkg) {
+                    "box"
+                } else {
+                    "ellipse"
+                };
+                res.push_str(&format!("    \"{}\" [shape={}];\n", pkg, shape));
+            }
+            let cut = cycle.break_points.first();
+            for edge in cycle.edges.iter() {
+                let mut attrs = vec![match edge.kind {
+                    DependencyKind::Runtime => "label=\"dep\"",
+                    DependencyKind::Build => "label=\"builddep\"",
+                }];
+                if cut.is_some_and(|c| c.contains(edge)) {
+                    attrs.push("style=dashed");
+                    attrs.push("color=red");
+                }
+                res.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [{}];\n",
+                    edge.from,
+                    edge.to,
+                    attrs.join(", ")
+                ));
+            }
+            res.push_str("  }\n");
+        }
+        res.push_str("}\n");
+
+        res
+    }
+}
+
+impl fmt::Display for CycleEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            DependencyKind::Runtime => "PKGDEP",
+            DependencyKind::Build => "BUILDDEP",
+        };
+        write!(f, "{} -[{}]-> {}", self.from, kind, self.to)
+    }
+}
+
+impl fmt::Display for CycleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for cycle in self.cycles.iter() {
+            writeln!(f, "Cycle: {}", cycle.packages.join(" "))?;
+            for edge in cycle.edges.iter() {
+                writeln!(f, "  {}", edge)?;
+            }
+            if !cycle.bootstrappable.is_empty() {
+                writeln!(f, "  Bootstrappable: {}", cycle.bootstrappable.join(" "))?;
+            }
+            let qualifier = if cycle.exact { "minimum" } else { "heuristic" };
+            for (i, set) in cycle.break_points.iter().enumerate() {
+                let set: Vec<String> = set.iter().map(|e| e.to_string()).collect();
+                writeln!(f, "  Break ({} #{}): {}", qualifier, i + 1, set.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    fn edge(from: &str, to: &str, kind: DependencyKind) -> CycleEdge {
+        CycleEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_dependency_cycles() {
+        let fixture = Fixture::new();
+        fixture
+            // a <-> b, where b only needs a to build
+            .package("extra-admin/a", "1.0", "PKGDEP=\"b\"")
+            .package("extra-admin/b", "1.0", "BUILDDEP=\"a\"")
+            // c -> d -> e -> c, with d also depending on c through a virtual name
+            .package(
+                "extra-admin/c",
+                "1.0",
+                "PKGDEP=\"d\"\nPKGPROV=\"libc-virt\"",
+            )
+            .package("extra-admin/d", "1.0", "PKGDEP=\"e libc-virt\"")
+            .package("extra-admin/e", "1.0", "PKGDEP=\"c\"")
+            .package("extra-admin/f", "1.0", "PKGDEP=\"a\"");
+        let report = fixture.load().dependency_cycles(None, &DependencyKind::ALL);
+
+        assert_eq!(report.cycles.len(), 2);
+        let two = &report.cycles[0];
+        assert_eq!(two.packages, vec!["a", "b"]);
+        assert!(two.exact);
+        assert_eq!(
+            two.break_points,
+            vec![
+                vec![edge("a", "b", DependencyKind::Runtime)],
+                vec![edge("b", "a", DependencyKind::Build)],
+            ]
+        );
+
+        let three = &report.cycles[1];
+        assert_eq!(three.packages, vec!["c", "d", "e"]);
+        assert_eq!(three.edges.len(), 4);
+        // Both c -> d -> e -> c and c -> d -> c go through c -> d
+        assert!(three.exact);
+        assert_eq!(
+            three.break_points,
+            vec![vec![edge("c", "d", DependencyKind::Runtime)]]
+        );
+
+        let runtime = fixture
+            .load()
+            .dependency_cycles(None, &[DependencyKind::Runtime]);
+        assert_eq!(runtime.cycles.len(), 1);
+        assert_eq!(runtime.cycles[0].packages, vec!["c", "d", "e"]);
+    }
+}
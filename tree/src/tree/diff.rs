@@ -0,0 +1,305 @@
+use super::Tree;
+use crate::package::{full_version, Package, PackageDepDependencies};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+/// Differences between two trees, from the old one to the new one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct TreeDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub version_changes: Vec<VersionChange>,
+    pub dependency_changes: Vec<DependencyChange>,
+    pub moves: Vec<PackageMove>,
+    pub section_changes: Vec<SectionChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FullVersion {
+    pub epoch: usize,
+    pub version: String,
+    pub release: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VersionChange {
+    pub name: String,
+    pub old: FullVersion,
+    pub new: FullVersion,
+}
+
+/// Entries added to or removed from one dependency field for one arch.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DependencyChange {
+    pub name: String,
+    /// Name of the defines variable, e.g. `PKGDEP`
+    pub field: String,
+    /// Lower-cased arch suffix, or `default`
+    pub arch: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A package whose directory moved, e.g. `extra-admin/foo` -> `app-admin/foo`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PackageMove {
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// A package whose `PKGSEC` changed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SectionChange {
+    pub name: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl Tree {
+    /// Compare this (old) tree with `other` (new).
+    pub fn diff(&self, other: &Tree) -> TreeDiff {
+        let mut res = TreeDiff::default();
+        let old_names: BTreeSet<&String> = self.packages.keys().collect();
+        let new_names: BTreeSet<&String> = other.packages.keys().collect();
+
+        res.added = new_names
+            .difference(&old_names)
+            .map(|n| n.to_string())
+            .collect();
+        res.removed = old_names
+            .difference(&new_names)
+            .map(|n| n.to_string())
+            .collect();
+
+        for name in old_names.intersection(&new_names) {
+            let old = &self.packages[*name];
+            let new = &other.packages[*name];
+
+            let (old_version, new_version) = (FullVersion::of(old), FullVersion::of(new));
+            if old_version != new_version {
+                res.version_changes.push(VersionChange {
+                    name: name.to_string(),
+                    old: old_version,
+                    new: new_version,
+                });
+            }
+
+            for ((field, old_deps), (_, new_deps)) in old
+                .dependency_fields()
+                .iter()
+                .zip(new.dependency_fields().iter())
+            {
+                diff_dependencies(name, field, old_deps, new_deps, &mut res.dependency_changes);
+            }
+
+            let (old_location, new_location) = (location(old), location(new));
+            if old_location != new_location {
+                res.moves.push(PackageMove {
+                    name: name.to_string(),
+                    old: old_location,
+                    new: new_location,
+                });
+            }
+
+            if old.pkg_section != new.pkg_section {
+                res.section_changes.push(SectionChange {
+                    name: name.to_string(),
+                    old: old.pkg_section.clone(),
+                    new: new.pkg_section.clone(),
+                });
+            }
+        }
+
+        res
+    }
+}
+
+impl TreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.version_changes.is_empty()
+            && self.dependency_changes.is_empty()
+            && self.moves.is_empty()
+            && self.section_changes.is_empty()
+    }
+
+    /// Every package that exists in both trees and changed in any way.
+    pub fn changed_packages(&self) -> BTreeSet<&str> {
+        let mut res = BTreeSet::new();
+        res.extend(self.version_changes.iter().map(|c| c.name.as_str()));
+        res.extend(self.dependency_changes.iter().map(|c| c.name.as_str()));
+        res.extend(self.moves.iter().map(|c| c.name.as_str()));
+        res.extend(self.section_changes.iter().map(|c| c.name.as_str()));
+        res
+    }
+}
+
+impl FullVersion {
+    pub(super) fn of(pkg: &Package) -> Self {
+        FullVersion {
+            epoch: pkg.epoch,
+            version: pkg.version.clone(),
+            release: pkg.release.clone(),
+        }
+    }
+}
+
+impl fmt::Display for FullVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = full_version(self.epoch, &self.version, &self.release);
+        write!(f, "{}", version)
+    }
+}
+
+impl fmt::Display for TreeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in self.added.iter() {
+            writeln!(f, "+ {}", name)?;
+        }
+        for name in self.removed.iter() {
+            writeln!(f, "- {}", name)?;
+        }
+        for c in self.version_changes.iter() {
+            writeln!(f, "~ {}: {} -> {}", c.name, c.old, c.new)?;
+        }
+        for c in self.dependency_changes.iter() {
+            let arch = if c.arch == "default" {
+                String::new()
+            } else {
+                format!("__{}", c.arch.to_uppercase())
+            };
+            let mut changes: Vec<String> = c.added.iter().map(|d| format!("+{}", d)).collect();
+            changes.extend(c.removed.iter().map(|d| format!("-{}", d)));
+            writeln!(f, "~ {}: {}{} {}", c.name, c.field, arch, changes.join(" "))?;
+        }
+        for c in self.moves.iter() {
+            writeln!(f, "~ {}: moved {} -> {}", c.name, c.old, c.new)?;
+        }
+        for c in self.section_changes.iter() {
+            writeln!(f, "~ {}: PKGSEC {} -> {}", c.name, c.old, c.new)?;
+        }
+        Ok(())
+    }
+}
+
+fn diff_dependencies(
+    name: &str,
+    field: &str,
+    old: &PackageDepDependencies,
+    new: &PackageDepDependencies,
+    res: &mut Vec<DependencyChange>,
+) {
+    let archs: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for arch in archs {
+        let old_deps = dependency_strings(old, arch);
+        let new_deps = dependency_strings(new, arch);
+        // Keep the order they appear in defines
+        let added: Vec<String> = new_deps
+            .iter()
+            .filter(|d| !old_deps.contains(*d))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = old_deps
+            .iter()
+            .filter(|d| !new_deps.contains(*d))
+            .cloned()
+            .collect();
+        if !added.is_empty() || !removed.is_empty() {
+            res.push(DependencyChange {
+                name: name.to_string(),
+                field: field.to_string(),
+                arch: arch.to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+}
+
+fn dependency_strings(deps: &PackageDepDependencies, arch: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    deps.get(arch)
+        .into_iter()
+        .flatten()
+        .map(|d| d.to_string())
+        .filter(|d| seen.insert(d.clone()))
+        .collect()
+}
+
+/// extra-admin/packagekit
+fn location(pkg: &Package) -> String {
+    format!("{}-{}/{}", pkg.category, pkg.section, pkg.directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    #[test]
+    fn test_diff() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "PKGDEP=\"bar\"")
+            .package("extra-admin/bar", "2.0", "")
+            .package("extra-utils/baz", "0.1", "");
+        let old = fixture.load();
+        fixture
+            .package("extra-admin/foo", "1.1", "PKGDEP=\"bar qux\"")
+            .remove("extra-admin/bar")
+            .remove("extra-utils/baz")
+            .package("app-utils/baz", "0.1", "")
+            .package("extra-admin/qux", "1.0", "");
+        let new = fixture.load();
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec!["qux"]);
+        assert_eq!(diff.removed, vec!["bar"]);
+        assert_eq!(
+            diff.version_changes,
+            vec![VersionChange {
+                name: "foo".to_string(),
+                old: FullVersion {
+                    epoch: 0,
+                    version: "1.0".to_string(),
+                    release: "0".to_string(),
+                },
+                new: FullVersion {
+                    epoch: 0,
+                    version: "1.1".to_string(),
+                    release: "0".to_string(),
+                },
+            }]
+        );
+        assert_eq!(
+            diff.dependency_changes,
+            vec![DependencyChange {
+                name: "foo".to_string(),
+                field: "PKGDEP".to_string(),
+                arch: "default".to_string(),
+                added: vec!["qux".to_string()],
+                removed: vec![],
+            }]
+        );
+        assert_eq!(
+            diff.moves,
+            vec![PackageMove {
+                name: "baz".to_string(),
+                old: "extra-utils/baz".to_string(),
+                new: "app-utils/baz".to_string(),
+            }]
+        );
+        assert!(diff.section_changes.is_empty());
+        assert_eq!(
+            diff.changed_packages().into_iter().collect::<Vec<_>>(),
+            vec!["baz", "foo"]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use super::LoadIssue;
 use crate::package::PackageError;
 use abbs_meta_apml::ParseError;
 use std::fmt;
@@ -7,6 +8,8 @@ pub enum TreeError {
     FsError(String),
     ParseError(ParseError),
     PackageError(PackageError),
+    LoadError(Box<LoadIssue>),
+    GitError(String),
 }
 
 impl From<walkdir::Error> for TreeError {
@@ -21,6 +24,13 @@ impl From<std::io::Error> for TreeError {
     }
 }
 
+#[cfg(feature = "git")]
+impl From<git2::Error> for TreeError {
+    fn from(err: git2::Error) -> Self {
+        TreeError::GitError(err.message().to_string())
+    }
+}
+
 impl From<ParseError> for TreeError {
     fn from(err: ParseError) -> Self {
         TreeError::ParseError(err)
@@ -35,6 +45,14 @@ impl From<PackageError> for TreeError {
 
 impl fmt::Display for TreeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "")
+        match self {
+            TreeError::FsError(e) => write!(f, "{}", e),
+            TreeError::ParseError(e) => write!(f, "{}", e),
+            TreeError::PackageError(e) => write!(f, "{}", e),
+            TreeError::LoadError(e) => write!(f, "{}", e),
+            TreeError::GitError(e) => write!(f, "{}", e),
+        }
     }
 }
+
+impl std::error::Error for TreeError {}
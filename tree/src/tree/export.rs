@@ -0,0 +1,331 @@
+use super::{revdeps::dependency_names, Tree};
+use crate::package::{Package, PackageDepDependencies};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EdgeKind {
+    Depends,
+    BuildDepends,
+    Recommends,
+    Suggests,
+    Breaks,
+    Replaces,
+    Conflicts,
+    Provides,
+}
+
+impl EdgeKind {
+    pub const ALL: [EdgeKind; 8] = [
+        EdgeKind::Depends,
+        EdgeKind::BuildDepends,
+        EdgeKind::Recommends,
+        EdgeKind::Suggests,
+        EdgeKind::Breaks,
+        EdgeKind::Replaces,
+        EdgeKind::Conflicts,
+        EdgeKind::Provides,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::Depends => "dep",
+            EdgeKind::BuildDepends => "builddep",
+            EdgeKind::Recommends => "recommends",
+            EdgeKind::Suggests => "suggests",
+            EdgeKind::Breaks => "breaks",
+            EdgeKind::Replaces => "replaces",
+            EdgeKind::Conflicts => "conflicts",
+            EdgeKind::Provides => "provides",
+        }
+    }
+
+    fn field<'a>(&self, pkg: &'a Package) -> &'a PackageDepDependencies {
+        match self {
+            EdgeKind::Depends => &pkg.dependencies,
+            EdgeKind::BuildDepends => &pkg.build_dependencies,
+            EdgeKind::Recommends => &pkg.recommends,
+            EdgeKind::Suggests => &pkg.package_suggests,
+            EdgeKind::Breaks => &pkg.package_breaks,
+            EdgeKind::Replaces => &pkg.package_replaces,
+            EdgeKind::Conflicts => &pkg.conflicts,
+            EdgeKind::Provides => &pkg.package_provides,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphOptions {
+    pub edge_kinds: Vec<EdgeKind>,
+    /// Only follow entries that apply on this arch, or every entry if `None`
+    pub arch: Option<String>,
+    /// How many edges away from the roots to go, or the whole closure if `None`
+    pub depth: Option<usize>,
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        GraphOptions {
+            edge_kinds: vec![EdgeKind::Depends, EdgeKind::BuildDepends],
+            arch: None,
+            depth: None,
+        }
+    }
+}
+
+/// A subgraph of the tree, ready to be exported.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    pub id: String,
+    /// `None` for virtual or missing packages
+    pub version: Option<String>,
+    /// e.g. `desktop-kde`
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: EdgeKind,
+}
+
+impl Tree {
+    /// Packages under a category and section, e.g. `desktop-kde`.
+    pub fn under<'a>(&'a self, category_section: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter()
+            .filter(move |p| format!("{}-{}", p.category, p.section) == category_section)
+    }
+
+    /// Walk the graph from `roots` along `options.edge_kinds`.
+    pub fn subgraph(&self, roots: &[&str], options: &GraphOptions) -> DependencyGraph {
+        let arch = options.arch.as_deref();
+        let providers = self.provider_index();
+        let mut depth_of: BTreeMap<String, usize> = BTreeMap::new();
+        let mut edges = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        for root in roots {
+            depth_of.insert(root.to_string(), 0);
+            queue.push_back(root.to_string());
+        }
+
+        while let Some(current) = queue.pop_front() {
+            let depth = depth_of[&current];
+            let expand = options.depth.is_none_or(|max| depth < max);
+            let pkg = match self.packages.get(&current) {
+                Some(pkg) => pkg,
+                None if !options.edge_kinds.contains(&EdgeKind::Provides) => continue,
+                None => {
+                    // Virtual names lead to their providers
+                    for provider in providers.get(current.as_str()).into_iter().flatten() {
+                        if !depth_of.contains_key(*provider) {
+                            if !expand {
+                                continue;
+                            }
+                            depth_of.insert(provider.to_string(), depth + 1);
+                            queue.push_back(provider.to_string());
+                        }
+                        edges.insert(GraphEdge {
+                            source: provider.to_string(),
+                            target: current.clone(),
+                            kind: EdgeKind::Provides,
+                        });
+                    }
+                    continue;
+                }
+            };
+            if arch.is_some_and(|a| !pkg.is_available_on(a)) {
+                continue;
+            }
+            for kind in options.edge_kinds.iter() {
+                for target in dependency_names(kind.field(pkg), arch, &self.arches) {
+                    if !depth_of.contains_key(target) {
+                        if !expand {
+                            continue;
+                        }
+                        depth_of.insert(target.to_string(), depth + 1);
+                        queue.push_back(target.to_string());
+                    }
+                    edges.insert(GraphEdge {
+                        source: current.clone(),
+                        target: target.to_string(),
+                        kind: *kind,
+                    });
+                }
+            }
+        }
+
+        DependencyGraph {
+            nodes: depth_of
+                .into_keys()
+                .map(|id| {
+                    let pkg = self.packages.get(&id);
+                    GraphNode {
+                        version: pkg.map(|p| p.full_version()),
+                        location: pkg.map(|p| format!("{}-{}", p.category, p.section)),
+                        id,
+                    }
+                })
+                .collect(),
+            edges: edges.into_iter().collect(),
+        }
+    }
+}
+
+impl DependencyGraph {
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph packages {\n");
+        for node in self.nodes.iter() {
+            match &node.version {
+                Some(version) => res.push_str(&format!(
+                    "  \"{}\" [label=\"{}\\n{}\"];\n",
+                    dot_escape(&node.id),
+                    dot_escape(&node.id),
+                    dot_escape(version)
+                )),
+                None => res.push_str(&format!("  \"{}\" [style=dashed];\n", dot_escape(&node.id))),
+            }
+        }
+        for edge in self.edges.iter() {
+            res.push_str(&format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                dot_escape(&edge.source),
+                dot_escape(&edge.target),
+                edge.kind.name()
+            ));
+        }
+        res.push_str("}\n");
+
+        res
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut res = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"version\" for=\"node\" attr.name=\"version\" attr.type=\"string\"/>\n",
+            "  <key id=\"location\" for=\"node\" attr.name=\"location\" attr.type=\"string\"/>\n",
+            "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <graph id=\"packages\" edgedefault=\"directed\">\n",
+        ));
+        for node in self.nodes.iter() {
+            res.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&node.id)));
+            if let Some(version) = &node.version {
+                res.push_str(&format!(
+                    "      <data key=\"version\">{}</data>\n",
+                    xml_escape(version)
+                ));
+            }
+            if let Some(location) = &node.location {
+                res.push_str(&format!(
+                    "      <data key=\"location\">{}</data>\n",
+                    xml_escape(location)
+                ));
+            }
+            res.push_str("    </node>\n");
+        }
+        for edge in self.edges.iter() {
+            res.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n      <data key=\"kind\">{}</data>\n    </edge>\n",
+                xml_escape(&edge.source),
+                xml_escape(&edge.target),
+                edge.kind.name()
+            ));
+        }
+        res.push_str("  </graph>\n</graphml>\n");
+
+        res
+    }
+
+    /// The node-link format understood by networkx and d3.
+    pub fn to_node_link_json(&self) -> serde_json::Value {
+        json!({
+            "directed": true,
+            "multigraph": true,
+            "graph": {},
+            "nodes": self.nodes.iter().map(|n| json!({
+                "id": n.id,
+                "version": n.version,
+                "location": n.location,
+            })).collect::<Vec<_>>(),
+            "links": self.edges.iter().map(|e| json!({
+                "source": e.source,
+                "target": e.target,
+                "kind": e.kind.name(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    fn edge(source: &str, target: &str, kind: EdgeKind) -> GraphEdge {
+        GraphEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_subgraph_provides() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/app", "1.0", "PKGDEP=\"libvirt-name\"")
+            .package(
+                "core-libs/provider",
+                "1.0",
+                "PKGPROV=\"libvirt-name\"\nPKGDEP=\"base\"",
+            )
+            .package("core-libs/base", "1.0", "");
+        let tree = fixture.load();
+        let ids = |graph: &DependencyGraph| -> Vec<String> {
+            graph.nodes.iter().map(|n| n.id.clone()).collect()
+        };
+
+        // Virtual names are dead ends without Provides
+        let graph = tree.subgraph(&["app"], &GraphOptions::default());
+        assert_eq!(ids(&graph), vec!["app", "libvirt-name"]);
+        assert_eq!(
+            graph.edges,
+            vec![edge("app", "libvirt-name", EdgeKind::Depends)]
+        );
+
+        let options = GraphOptions {
+            edge_kinds: vec![EdgeKind::Depends, EdgeKind::Provides],
+            ..Default::default()
+        };
+        let graph = tree.subgraph(&["app"], &options);
+        assert_eq!(ids(&graph), vec!["app", "base", "libvirt-name", "provider"]);
+        assert_eq!(
+            graph.edges,
+            vec![
+                edge("app", "libvirt-name", EdgeKind::Depends),
+                edge("provider", "base", EdgeKind::Depends),
+                edge("provider", "libvirt-name", EdgeKind::Provides),
+            ]
+        );
+    }
+}
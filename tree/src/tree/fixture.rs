@@ -0,0 +1,97 @@
+//! Throwaway trees on disk for tests.
+
+use super::{LoadOptions, LoadPolicy, Tree, TreeLayout};
+use crate::package::{ArchRegistry, SectionRegistry};
+
+#[cfg(feature = "git")]
+use git2::{Commit, IndexAddOption, Repository, Signature};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+pub(crate) struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Fixture {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root().join(path)
+    }
+
+    /// Write `content` to `path`, relative to the root.
+    pub fn write(&self, path: &str, content: &str) -> &Self {
+        let path = self.path(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        self
+    }
+
+    /// A directory such as `extra-admin/foo` building a single package named
+    /// after it, with `defines` added to the defines file.
+    pub fn package(&self, dir: &str, version: &str, defines: &str) -> &Self {
+        let (category, name) = dir.split_once('/').unwrap();
+        let section = category.split_once('-').unwrap().1;
+        self.write(&format!("{}/spec", dir), &format!("VER={}\n", version));
+        self.write(
+            &format!("{}/autobuild/defines", dir),
+            &format!(
+                "PKGNAME={}\nPKGSEC={}\nPKGDES=\"{}\"\n{}\n",
+                name, section, name, defines
+            ),
+        )
+    }
+
+    pub fn remove(&self, path: &str) -> &Self {
+        let path = self.path(path);
+        if path.is_dir() {
+            fs::remove_dir_all(path).unwrap();
+        } else {
+            fs::remove_file(path).unwrap();
+        }
+        self
+    }
+
+    /// Like `LoadOptions::default`, but with the built-in registries, so that
+    /// results do not depend on the autobuild4 installed on the host.
+    pub fn options() -> LoadOptions {
+        LoadOptions {
+            policy: LoadPolicy::default(),
+            layout: TreeLayout::default(),
+            sections: SectionRegistry::default(),
+            arches: ArchRegistry::default(),
+        }
+    }
+
+    pub fn load(&self) -> Tree {
+        Tree::load(self.root(), &Self::options()).unwrap().0
+    }
+}
+
+/// Commit everything under the work tree of `repo` on top of `parents`, and
+/// point `branch` at the result.
+#[cfg(feature = "git")]
+pub fn commit<'a>(repo: &'a Repository, parents: &[&Commit], branch: &str) -> Commit<'a> {
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+    index.update_all(["*"], None).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Tester", "tester@example.com").unwrap();
+    let id = repo
+        .commit(None, &sig, &sig, branch, &tree, parents)
+        .unwrap();
+    let commit = repo.find_commit(id).unwrap();
+    repo.branch(branch, &commit, true).unwrap();
+    commit
+}
@@ -0,0 +1,248 @@
+use super::{
+    error::TreeError, load_dir, report::Recorder, LoadIssue, LoadOptions, PackageGroup,
+    TopicReport, Tree, TreeLayout, TreeLoadReport, GROUPS_DIR,
+};
+
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+impl Tree {
+    /// Load a tree as of `rev` in the git repository at `repo_path`, reading
+    /// blobs from the object database instead of a checkout.
+    pub fn from_git(repo_path: &Path, rev: &str) -> Result<Self, TreeError> {
+        let (tree, _) = Self::load_git(repo_path, rev, &LoadOptions::default())?;
+        Ok(tree)
+    }
+
+    /// Like `Tree::load`, at `rev`. Paths in the result and in issues are
+    /// below `repo_path`, as if `rev` were checked out there.
+    pub fn load_git(
+        repo_path: &Path,
+        rev: &str,
+        options: &LoadOptions,
+    ) -> Result<(Self, TreeLoadReport), TreeError> {
+        let repo = Repository::open(repo_path)?;
+        let git_tree = repo.revparse_single(rev)?.peel_to_tree()?;
+        let mut recorder = Recorder::new(options.policy);
+        let blobs = walk_git(&git_tree, Path::new(""), &options.layout)?;
+        let mut res = load_blobs(&repo, repo_path, rev, &blobs, options, &mut recorder)?;
+        res.groups = load_groups(&repo, &git_tree, repo_path, &mut recorder)?;
+
+        Ok((res, recorder.report))
+    }
+
+    /// Compare revision `topic` against revision `stable`, see `Tree::compare_topic`.
+    pub fn compare_topic_git(
+        repo_path: &Path,
+        stable: &str,
+        topic: &str,
+        options: &LoadOptions,
+    ) -> Result<TopicReport, TreeError> {
+        let repo = Repository::open(repo_path)?;
+        let stable_id = repo.revparse_single(stable)?.peel_to_commit()?.id();
+        let topic_id = repo.revparse_single(topic)?.peel_to_commit()?.id();
+        let base = repo.merge_base(stable_id, topic_id)?.to_string();
+        let (base, _) = Self::load_git(repo_path, &base, options)?;
+        let (stable, _) = Self::load_git(repo_path, &stable_id.to_string(), options)?;
+        let (topic, _) = Self::load_git(repo_path, &topic_id.to_string(), options)?;
+
+        Ok(base.compare_topic(&stable, &topic))
+    }
+}
+
+/// Load the package directories found by `walk_git` as a tree rooted at `repo_path`.
+/// `rev` only serves error messages.
+pub(super) fn load_blobs(
+    repo: &Repository,
+    repo_path: &Path,
+    rev: &str,
+    blobs: &HashMap<PathBuf, Oid>,
+    options: &LoadOptions,
+    recorder: &mut Recorder,
+) -> Result<Tree, TreeError> {
+    let mut pkg_dirs: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for defines_path in blobs.keys().filter(|p| p.ends_with("defines")) {
+        let spec_path = match defines_path.parent().and_then(|p| p.parent()) {
+            Some(pkg_dir) => pkg_dir.join("spec"),
+            None => continue,
+        };
+        if !blobs.contains_key(&spec_path) {
+            recorder.record(LoadIssue::MissingSpec {
+                defines_path: repo_path.join(defines_path),
+                spec_path: repo_path.join(spec_path),
+            })?;
+            continue;
+        }
+        pkg_dirs
+            .entry(repo_path.join(spec_path))
+            .or_default()
+            .push(repo_path.join(defines_path));
+    }
+    // Keep the order `walk` would find them in
+    for defines_paths in pkg_dirs.values_mut() {
+        defines_paths.sort();
+    }
+
+    // `load_dir` records a blob it cannot read as `LoadIssue::UnreadableFile`
+    let read = |path: &Path| -> Result<String, TreeError> {
+        let oid = path
+            .strip_prefix(repo_path)
+            .ok()
+            .and_then(|p| blobs.get(p))
+            .ok_or_else(|| TreeError::GitError(format!("{} is not in {}", path.display(), rev)))?;
+        let blob = repo.find_blob(*oid)?;
+        String::from_utf8(blob.content().to_vec())
+            .map_err(|_| TreeError::GitError(format!("{} is not valid UTF-8", path.display())))
+    };
+    let mut res = Tree::empty(repo_path, options);
+    let mut defined_at: HashMap<String, PathBuf> = HashMap::new();
+    for (spec_path, defines_paths) in pkg_dirs {
+        if let Some(dir) = load_dir(options, spec_path, defines_paths, &read, recorder)? {
+            res.insert_dir(dir, &mut defined_at, recorder)?;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Like `groups::load_groups`, from `git_tree`.
+fn load_groups(
+    repo: &Repository,
+    git_tree: &git2::Tree,
+    repo_path: &Path,
+    recorder: &mut Recorder,
+) -> Result<BTreeMap<String, PackageGroup>, TreeError> {
+    let mut res = BTreeMap::new();
+    let groups_tree = match git_tree.get_path(Path::new(GROUPS_DIR)) {
+        Ok(entry) => match entry.to_object(repo)?.into_tree() {
+            Ok(tree) => tree,
+            Err(_) => return Ok(res),
+        },
+        Err(_) => return Ok(res),
+    };
+    for entry in groups_tree.iter() {
+        let name = match entry.name() {
+            Some(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        if entry.kind() != Some(ObjectType::Blob) {
+            continue;
+        }
+        let blob = repo.find_blob(entry.id())?;
+        let path = repo_path.join(GROUPS_DIR).join(name);
+        let content = match String::from_utf8(blob.content().to_vec()) {
+            Ok(content) => content,
+            Err(e) => {
+                recorder.record(LoadIssue::UnreadableGroup {
+                    path,
+                    error: e.to_string(),
+                })?;
+                continue;
+            }
+        };
+        res.insert(name.to_string(), PackageGroup::parse(name, &path, &content));
+    }
+
+    Ok(res)
+}
+
+/// Every `spec` and `defines` blob as deep in `tree` as `walk` would look, by path
+/// relative to the repository root. `tree` is found at `prefix` in the repository.
+pub(super) fn walk_git(
+    tree: &git2::Tree,
+    prefix: &Path,
+    layout: &TreeLayout,
+) -> Result<HashMap<PathBuf, Oid>, TreeError> {
+    let mut res = HashMap::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        let name = match entry.name() {
+            Some(name) => name,
+            None => return TreeWalkResult::Skip,
+        };
+        let depth = prefix.components().count() + dir.matches('/').count() + 1;
+        match entry.kind() {
+            Some(ObjectType::Tree) if depth == 1 && layout.is_non_package_dir(name) => {
+                TreeWalkResult::Skip
+            }
+            Some(ObjectType::Tree) if depth >= 4 => TreeWalkResult::Skip,
+            Some(ObjectType::Blob) if name == "spec" || name == "defines" => {
+                // Symlinks are blobs too, but hold a path rather than content
+                if entry.filemode() != i32::from(git2::FileMode::Link) {
+                    res.insert(prefix.join(dir).join(name), entry.id());
+                }
+                TreeWalkResult::Ok
+            }
+            _ => TreeWalkResult::Ok,
+        }
+    })?;
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{
+        fixture::{commit, Fixture},
+        LoadPolicy,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_load_git_unreadable() {
+        let fixture = Fixture::new();
+        let repo = Repository::init(fixture.root()).unwrap();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .package("extra-admin/bar", "1.0", "");
+        let defines_path = fixture.path("extra-admin/bar/autobuild/defines");
+        fs::write(&defines_path, b"PKGNAME=bar\nPKGDES=\"\xff\"\n").unwrap();
+        commit(&repo, &[], "master");
+
+        let (tree, report) = Tree::load_git(fixture.root(), "master", &Fixture::options()).unwrap();
+        assert!(tree.get("foo").is_some());
+        assert!(tree.get("bar").is_none());
+        match report.issues.as_slice() {
+            [issue @ LoadIssue::UnreadableFile { .. }] => assert_eq!(issue.path(), &defines_path),
+            issues => panic!("unexpected issues: {:?}", issues),
+        }
+
+        let options = LoadOptions {
+            policy: LoadPolicy::FailFast,
+            ..Fixture::options()
+        };
+        assert!(Tree::load_git(fixture.root(), "master", &options).is_err());
+    }
+
+    #[test]
+    fn test_compare_topic_git() {
+        let fixture = Fixture::new();
+        let repo = Repository::init(fixture.root()).unwrap();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .package("extra-admin/bar", "1.0", "");
+        let base = commit(&repo, &[], "base");
+        // Stable moves on after the topic branched off
+        fixture.package("extra-admin/foo", "2.0", "");
+        commit(&repo, &[&base], "stable");
+        fixture
+            .package("extra-admin/foo", "1.1", "")
+            .package("extra-admin/bar", "1.1", "");
+        commit(&repo, &[&base], "topic");
+
+        let report =
+            Tree::compare_topic_git(fixture.root(), "stable", "topic", &Fixture::options())
+                .unwrap();
+        assert_eq!(report.touched, vec!["bar", "foo"]);
+        // foo 1.1 is newer than where the topic started, but older than in stable
+        let regressions: Vec<String> = report
+            .regressions
+            .iter()
+            .map(|c| format!("{} {} -> {}", c.name, c.old, c.new))
+            .collect();
+        assert_eq!(regressions, vec!["foo 2.0 -> 1.1"]);
+    }
+}
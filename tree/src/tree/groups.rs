@@ -0,0 +1,369 @@
+use super::{
+    error::TreeError, plan::build_stages, report::Recorder, BuildStage, DependencyKind, LoadIssue,
+    Tree,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Top-level directory of a tree holding group files.
+pub const GROUPS_DIR: &str = "groups";
+
+/// A named package set from `groups/<name>`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PackageGroup {
+    pub name: String,
+    pub path: PathBuf,
+    pub entries: Vec<GroupEntry>,
+}
+
+/// One line of a group file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GroupEntry {
+    /// `foo`
+    Package(String),
+    /// `extra-admin/foo`, standing for every package built from it
+    Directory(String),
+    /// `groups/bar`
+    Group(String),
+}
+
+/// A group entry that does not make sense against the rest of the tree, or a
+/// group that is not there at all.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GroupIssue {
+    UnknownPackage {
+        group: String,
+        name: String,
+    },
+    UnknownDirectory {
+        group: String,
+        directory: String,
+    },
+    UnknownGroup {
+        group: String,
+        name: String,
+    },
+    /// Groups including each other, starting and ending with the same group
+    Cycle {
+        groups: Vec<String>,
+    },
+    /// No group file by this name
+    NotFound {
+        name: String,
+    },
+}
+
+impl PackageGroup {
+    /// Parse a group file: one entry per line, `#` starts a comment.
+    pub fn parse(name: &str, path: &Path, content: &str) -> Self {
+        let entries = content
+            .lines()
+            .map(|l| l.split('#').next().unwrap_or_default().trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| match l.strip_prefix("groups/") {
+                Some(group) => GroupEntry::Group(group.to_string()),
+                None if l.contains('/') => {
+                    GroupEntry::Directory(l.trim_end_matches('/').to_string())
+                }
+                None => GroupEntry::Package(l.to_string()),
+            })
+            .collect();
+
+        PackageGroup {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+}
+
+/// Read every group file under `root/groups`, if there are any.
+pub(super) fn load_groups(
+    root: &Path,
+    recorder: &mut Recorder,
+) -> Result<BTreeMap<String, PackageGroup>, TreeError> {
+    let mut res = BTreeMap::new();
+    let dir = root.join(GROUPS_DIR);
+    if !dir.is_dir() {
+        return Ok(res);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || !entry.file_type()?.is_file() {
+            continue;
+        }
+        let content = match fs::read_to_string(entry.path()) {
+            Ok(content) => content,
+            Err(e) => {
+                recorder.record(LoadIssue::UnreadableGroup {
+                    path: entry.path(),
+                    error: e.to_string(),
+                })?;
+                continue;
+            }
+        };
+        res.insert(
+            name.clone(),
+            PackageGroup::parse(&name, &entry.path(), &content),
+        );
+    }
+
+    Ok(res)
+}
+
+impl Tree {
+    pub fn group(&self, name: &str) -> Option<&PackageGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &PackageGroup> {
+        self.groups.values()
+    }
+
+    /// Every package in group `name`, including nested groups. Entries that do
+    /// not resolve are left out; see `Tree::check_groups`.
+    pub fn group_packages(&self, name: &str) -> Result<BTreeSet<&str>, GroupIssue> {
+        if !self.groups.contains_key(name) {
+            return Err(GroupIssue::NotFound {
+                name: name.to_string(),
+            });
+        }
+        let mut res = BTreeSet::new();
+        self.expand_group(name, &mut Vec::new(), &mut res, &mut Vec::new())?;
+        Ok(res)
+    }
+
+    /// Check that every entry of every group resolves, and that no groups include each other.
+    pub fn check_groups(&self) -> Vec<GroupIssue> {
+        let mut res = Vec::new();
+        for name in self.groups.keys() {
+            let mut issues = Vec::new();
+            let cycle = self.expand_group(name, &mut Vec::new(), &mut BTreeSet::new(), &mut issues);
+            res.extend(issues);
+            // Every group in a cycle runs into it
+            if let Err(issue) = cycle {
+                if !res.contains(&issue) {
+                    res.push(issue);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Stages to build group `name` in, only counting dependencies between its packages.
+    pub fn group_stages(
+        &self,
+        name: &str,
+        arch: Option<&str>,
+        kinds: &[DependencyKind],
+    ) -> Result<Vec<BuildStage>, GroupIssue> {
+        let packages = self.group_packages(name)?;
+        let graph = self.dependency_graph(&packages, arch, kinds);
+        Ok(build_stages(&packages, graph))
+    }
+
+    /// Add the packages of group `name` to `res`. Only entries of the outermost
+    /// group end up in `issues`, nested groups are checked on their own.
+    fn expand_group<'a>(
+        &'a self,
+        name: &str,
+        stack: &mut Vec<String>,
+        res: &mut BTreeSet<&'a str>,
+        issues: &mut Vec<GroupIssue>,
+    ) -> Result<(), GroupIssue> {
+        if let Some(pos) = stack.iter().position(|g| g == name) {
+            // Start from the same group wherever the cycle was entered
+            let mut groups = stack[pos..].to_vec();
+            let first = (0..groups.len()).min_by_key(|i| &groups[*i]).unwrap_or(0);
+            groups.rotate_left(first);
+            groups.push(groups[0].clone());
+            return Err(GroupIssue::Cycle { groups });
+        }
+        let group = match self.groups.get(name) {
+            Some(group) => group,
+            None => return Ok(()),
+        };
+
+        stack.push(name.to_string());
+        let outermost = stack.len() == 1;
+        for entry in group.entries.iter() {
+            match entry {
+                GroupEntry::Package(pkg) => match self.packages.get_key_value(pkg) {
+                    Some((pkg, _)) => {
+                        res.insert(pkg);
+                    }
+                    None if outermost => issues.push(GroupIssue::UnknownPackage {
+                        group: name.to_string(),
+                        name: pkg.to_string(),
+                    }),
+                    None => (),
+                },
+                GroupEntry::Directory(directory) => {
+                    let spec_path = self.root.join(directory).join("spec");
+                    match self.sources.get(spec_path.to_string_lossy().as_ref()) {
+                        Some(source) => res.extend(source.package_names()),
+                        None if outermost => issues.push(GroupIssue::UnknownDirectory {
+                            group: name.to_string(),
+                            directory: directory.to_string(),
+                        }),
+                        None => (),
+                    }
+                }
+                GroupEntry::Group(nested) => {
+                    if !self.groups.contains_key(nested) && outermost {
+                        issues.push(GroupIssue::UnknownGroup {
+                            group: name.to_string(),
+                            name: nested.to_string(),
+                        });
+                    }
+                    self.expand_group(nested, stack, res, issues)?;
+                }
+            }
+        }
+        stack.pop();
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for GroupIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupIssue::UnknownPackage { group, name } => {
+                write!(f, "groups/{}: no package named {}", group, name)
+            }
+            GroupIssue::UnknownDirectory { group, directory } => {
+                write!(f, "groups/{}: no package directory {}", group, directory)
+            }
+            GroupIssue::UnknownGroup { group, name } => {
+                write!(f, "groups/{}: no group named {}", group, name)
+            }
+            GroupIssue::Cycle { groups } => {
+                write!(f, "groups include each other: {}", groups.join(" -> "))
+            }
+            GroupIssue::NotFound { name } => write!(f, "no group named {}", name),
+        }
+    }
+}
+
+impl std::error::Error for GroupIssue {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    #[test]
+    fn test_parse_group() {
+        let group = PackageGroup::parse(
+            "plasma",
+            Path::new("groups/plasma"),
+            "# Plasma desktop\nkwin\n\nextra-admin/packagekit/\ngroups/kf5 # frameworks\n",
+        );
+        assert_eq!(
+            group.entries,
+            vec![
+                GroupEntry::Package("kwin".to_string()),
+                GroupEntry::Directory("extra-admin/packagekit".to_string()),
+                GroupEntry::Group("kf5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_group() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .write("groups/good", "foo\n");
+        fs::write(fixture.path("groups/bad"), b"foo\n\xff\n").unwrap();
+
+        let (tree, report) = Tree::load(fixture.root(), &Fixture::options()).unwrap();
+        assert!(tree.group("good").is_some());
+        assert!(tree.group("bad").is_none());
+        match report.issues.as_slice() {
+            [LoadIssue::UnreadableGroup { path, .. }] => {
+                assert_eq!(path, &fixture.path("groups/bad"))
+            }
+            issues => panic!("unexpected issues: {:?}", issues),
+        }
+    }
+
+    fn fixture() -> Fixture {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .package("extra-utils/baz", "1.0", "")
+            .write("core-libs/bar/spec", "VER=1.0\n")
+            .write(
+                "core-libs/bar/01-bar/defines",
+                "PKGNAME=bar\nPKGSEC=libs\nPKGDES=\"Bar\"\n",
+            )
+            .write(
+                "core-libs/bar/02-libbar/defines",
+                "PKGNAME=libbar\nPKGSEC=libs\nPKGDES=\"libbar\"\n",
+            )
+            .write("groups/base", "foo\ncore-libs/bar/\n")
+            .write("groups/desktop", "baz\ngroups/base\n")
+            .write("groups/a", "groups/b\n")
+            .write("groups/b", "groups/c\n")
+            .write("groups/c", "foo\ngroups/a\n")
+            .write(
+                "groups/broken",
+                "missing\nextra-admin/nope\ngroups/nope\nfoo\n",
+            );
+        fixture
+    }
+
+    #[test]
+    fn test_group_packages() {
+        let tree = fixture().load();
+        let packages = |name| {
+            tree.group_packages(name)
+                .map(|p| p.into_iter().collect::<Vec<_>>())
+        };
+
+        // A directory stands for every package built from it
+        assert_eq!(packages("base"), Ok(vec!["bar", "foo", "libbar"]));
+        assert_eq!(packages("desktop"), Ok(vec!["bar", "baz", "foo", "libbar"]));
+        assert_eq!(packages("broken"), Ok(vec!["foo"]));
+        assert_eq!(
+            packages("nope"),
+            Err(GroupIssue::NotFound {
+                name: "nope".to_string()
+            })
+        );
+        // Reported the same way wherever the cycle is entered
+        let cycle = GroupIssue::Cycle {
+            groups: vec!["a", "b", "c", "a"]
+                .into_iter()
+                .map(|g| g.to_string())
+                .collect(),
+        };
+        assert_eq!(packages("a"), Err(cycle.clone()));
+        assert_eq!(packages("b"), Err(cycle.clone()));
+        assert_eq!(packages("c"), Err(cycle));
+    }
+
+    #[test]
+    fn test_check_groups() {
+        let tree = fixture().load();
+        let issues: Vec<String> = tree.check_groups().iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "groups include each other: a -> b -> c -> a",
+                "groups/broken: no package named missing",
+                "groups/broken: no package directory extra-admin/nope",
+                "groups/broken: no group named nope",
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use super::{
+    error::TreeError,
+    git::{load_blobs, walk_git},
+    report::Recorder,
+    LoadIssue, LoadOptions, LoadPolicy, Tree, TreeDiff,
+};
+
+use git2::{Oid, Repository, Sort};
+use serde::{Serialize, Serializer};
+use std::path::{Path, PathBuf};
+
+/// A commit that changed the packages of a directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub commit: String,
+    /// Committer time, in seconds since the Unix epoch
+    pub time: i64,
+    pub author: String,
+    pub summary: String,
+    /// From the directory as of the previous entry to as of this commit
+    pub diff: TreeDiff,
+    /// Packages that failed to load at this commit, and so show up as removed
+    #[serde(serialize_with = "serialize_issues")]
+    pub issues: Vec<LoadIssue>,
+}
+
+fn serialize_issues<S: Serializer>(issues: &[LoadIssue], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(issues.iter().map(|i| i.to_string()))
+}
+
+impl Tree {
+    /// Walk the first-parent history of `rev` in the repository at `repo_path`,
+    /// oldest first, and list the commits that changed a package defined under
+    /// `dir`, e.g. `extra-admin/foo`. Changes that arrive through a merge are
+    /// attributed to the merge commit.
+    pub fn directory_history(
+        repo_path: &Path,
+        rev: &str,
+        dir: &Path,
+        options: &LoadOptions,
+    ) -> Result<Vec<HistoryEntry>, TreeError> {
+        let repo = Repository::open(repo_path)?;
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?;
+        revwalk.simplify_first_parent()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+
+        let mut res = Vec::new();
+        let mut last_oid: Option<Oid> = None;
+        let mut last = Tree::empty(repo_path, options);
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            let dir_oid = match commit.tree()?.get_path(dir) {
+                Ok(entry) => Some(entry.id()),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            // Most commits do not touch the directory at all
+            if dir_oid == last_oid {
+                continue;
+            }
+            last_oid = dir_oid;
+
+            let mut recorder = Recorder::new(LoadPolicy::CollectAll);
+            let current = match dir_oid {
+                Some(dir_oid) => {
+                    let blobs = match repo.find_tree(dir_oid) {
+                        Ok(git_tree) => walk_git(&git_tree, dir, &options.layout)?,
+                        // Not a directory, so no packages either
+                        Err(_) => Default::default(),
+                    };
+                    let commit_id = commit.id().to_string();
+                    load_blobs(&repo, repo_path, &commit_id, &blobs, options, &mut recorder)?
+                }
+                None => Tree::empty(repo_path, options),
+            };
+
+            let diff = last.diff(&current);
+            let issues = recorder.report.issues;
+            if !diff.is_empty() || !issues.is_empty() {
+                res.push(HistoryEntry {
+                    commit: commit.id().to_string(),
+                    time: commit.time().seconds(),
+                    author: commit.author().name().unwrap_or_default().to_string(),
+                    summary: commit.summary().unwrap_or_default().to_string(),
+                    diff,
+                    issues,
+                });
+            }
+            last = current;
+        }
+
+        Ok(res)
+    }
+
+    /// Like `Tree::directory_history`, for the directory defining `name` at `rev`.
+    pub fn package_history(
+        repo_path: &Path,
+        rev: &str,
+        name: &str,
+    ) -> Result<Vec<HistoryEntry>, TreeError> {
+        let tree = Tree::from_git(repo_path, rev)?;
+        let pkg = tree.get(name).ok_or_else(|| {
+            TreeError::GitError(format!("{} is not in the tree at {}", name, rev))
+        })?;
+        let dir: PathBuf = Path::new(&pkg.spec_path)
+            .parent()
+            .and_then(|d| d.strip_prefix(repo_path).ok())
+            .map(|d| d.to_path_buf())
+            .ok_or_else(|| {
+                TreeError::GitError(format!(
+                    "{} is not below {}",
+                    pkg.spec_path,
+                    repo_path.display()
+                ))
+            })?;
+
+        let options = tree.load_options();
+        Self::directory_history(repo_path, rev, &dir, &options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::{commit, Fixture};
+
+    #[test]
+    fn test_directory_history() {
+        let fixture = Fixture::new();
+        let repo = Repository::init(fixture.root()).unwrap();
+        fixture.package("extra-admin/bar", "1.0", "");
+        let c0 = commit(&repo, &[], "trunk");
+        fixture.package("extra-admin/foo", "1.0", "");
+        let c1 = commit(&repo, &[&c0], "trunk");
+        fixture.package("extra-admin/foo", "1.1", "");
+        let c2 = commit(&repo, &[&c1], "trunk");
+        fixture.package("extra-admin/foo", "1.1", "PKGDEP=\"bar\"");
+        let c3 = commit(&repo, &[&c2], "trunk");
+        // Not below extra-admin/foo
+        fixture.package("extra-admin/bar", "1.1", "");
+        let c4 = commit(&repo, &[&c3], "trunk");
+        fixture.package("extra-admin/foo", "1.2", "PKGDEP=\"bar\"");
+        let side = commit(&repo, &[&c4], "side");
+        let merge = commit(&repo, &[&c4, &side], "trunk");
+
+        let history = Tree::directory_history(
+            fixture.root(),
+            "trunk",
+            Path::new("extra-admin/foo"),
+            &Fixture::options(),
+        )
+        .unwrap();
+        let commits: Vec<String> = history.iter().map(|e| e.commit.clone()).collect();
+        let expected: Vec<String> = [&c1, &c2, &c3, &merge]
+            .iter()
+            .map(|c| c.id().to_string())
+            .collect();
+        // The side branch commit is only reachable through the merge
+        assert_eq!(commits, expected);
+        assert!(history.iter().all(|e| e.issues.is_empty()));
+
+        // The directory first appears
+        assert_eq!(history[0].diff.added, vec!["foo"]);
+        assert!(history[0].diff.removed.is_empty());
+
+        // A version bump
+        let bump = &history[1].diff;
+        assert_eq!(bump.version_changes.len(), 1);
+        assert_eq!(bump.version_changes[0].old.version, "1.0");
+        assert_eq!(bump.version_changes[0].new.version, "1.1");
+        assert!(bump.dependency_changes.is_empty());
+
+        // A dependency change
+        let deps = &history[2].diff;
+        assert!(deps.version_changes.is_empty());
+        assert_eq!(deps.dependency_changes.len(), 1);
+        assert_eq!(deps.dependency_changes[0].field, "PKGDEP");
+        assert_eq!(deps.dependency_changes[0].added, vec!["bar"]);
+
+        // Attributed to the merge, not the side branch commit
+        let merged = &history[3].diff;
+        assert_eq!(merged.version_changes.len(), 1);
+        assert_eq!(merged.version_changes[0].old.version, "1.1");
+        assert_eq!(merged.version_changes[0].new.version, "1.2");
+    }
+
+    #[test]
+    fn test_package_history() {
+        let fixture = Fixture::new();
+        let repo = Repository::init(fixture.root()).unwrap();
+        fixture.package("extra-admin/foo", "1.0", "");
+        let c0 = commit(&repo, &[], "trunk");
+        fixture.package("extra-admin/foo", "1.1", "");
+        let c1 = commit(&repo, &[&c0], "trunk");
+
+        let history = Tree::package_history(fixture.root(), "trunk", "foo").unwrap();
+        let commits: Vec<String> = history.iter().map(|e| e.commit.clone()).collect();
+        assert_eq!(commits, vec![c0.id().to_string(), c1.id().to_string()]);
+        assert!(Tree::package_history(fixture.root(), "trunk", "bar").is_err());
+    }
+}
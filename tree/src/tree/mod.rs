@@ -1,96 +1,443 @@
+mod cache;
+mod check;
+mod cycles;
+mod diff;
 pub mod error;
+mod export;
+#[cfg(test)]
+mod fixture;
+#[cfg(feature = "git")]
+mod git;
+mod groups;
+#[cfg(feature = "git")]
+mod history;
+mod plan;
+mod report;
+mod revdeps;
+mod topic;
+#[cfg(feature = "watch")]
+mod watch;
+pub use crate::layout::{PackageLocation, TreeLayout, ABBS_CATEGORIES, NON_PACKAGE_DIRS};
+pub use cache::CacheReport;
+pub use check::{DependencyIssue, DependencyRef, REQUIRING_FIELDS};
+pub use cycles::{CycleEdge, CycleReport, DependencyCycle};
+pub use diff::{
+    DependencyChange, FullVersion, PackageMove, SectionChange, TreeDiff, VersionChange,
+};
 use error::TreeError;
+pub use export::{DependencyGraph, EdgeKind, GraphEdge, GraphNode, GraphOptions};
+pub use groups::{GroupEntry, GroupIssue, PackageGroup, GROUPS_DIR};
+#[cfg(feature = "git")]
+pub use history::HistoryEntry;
+pub use plan::{BuildGroup, BuildStage, RebuildPlan};
+use report::Recorder;
+pub use report::{LoadIssue, LoadOptions, LoadPolicy, TreeLoadReport};
+pub use revdeps::{DependencyKind, ReverseDependencyIndex};
+pub use topic::TopicReport;
+#[cfg(feature = "watch")]
+pub use watch::{TreeEvent, TreeWatcher, WatchedTree};
 
-use super::package::Package;
+use super::package::{ArchRegistry, Package, SectionRegistry, SourcePackage, SubPackage};
 use abbs_meta_apml::parse;
 
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Tree {
+    root: PathBuf,
+    layout: TreeLayout,
+    #[serde(skip)]
+    sections: SectionRegistry,
+    #[serde(skip)]
+    arches: ArchRegistry,
     packages: HashMap<String, Package>,
+    /// Package directories keyed by spec path
+    sources: HashMap<String, SourcePackage>,
+    groups: BTreeMap<String, PackageGroup>,
 }
 
 impl Tree {
+    /// Load a tree, skipping every package that fails to load.
+    /// Use `Tree::load` to find out what was skipped.
     pub fn from(path: &Path) -> Result<Self, TreeError> {
-        let walker = walkdir::WalkDir::new(path).max_depth(4);
-        let mut pkg_dirs = Vec::new();
-        for entry in walker.into_iter() {
-            let file = entry?;
-            if file.file_name() == "defines" {
-                let pkg_dir = file
-                    .path()
-                    .parent()
-                    .ok_or_else(|| {
-                        TreeError::FsError(format!(
-                            "The directory of defines file {} is root.",
-                            file.path().display()
-                        ))
-                    })?
-                    .parent()
-                    .ok_or_else(|| {
-                        TreeError::FsError(format!(
-                            "The parent directory of defines file {} is root.",
-                            file.path().display()
-                        ))
-                    })?;
-                let spec_path = pkg_dir.join("spec");
-                if !spec_path.is_file() {
-                    return Err(TreeError::FsError(format!(
-                        "spec file not found at {} for {}",
-                        spec_path.display(),
-                        file.path().display()
-                    )));
-                }
-                pkg_dirs.push((spec_path, file.path().to_path_buf()));
+        let (tree, _) = Self::load(path, &LoadOptions::default())?;
+        Ok(tree)
+    }
+
+    pub fn load(path: &Path, options: &LoadOptions) -> Result<(Self, TreeLoadReport), TreeError> {
+        let mut recorder = Recorder::new(options.policy);
+        let pkg_dirs = walk(path, &options.layout, &mut recorder)?;
+
+        let mut res = Tree::empty(path, options);
+        let mut defined_at: HashMap<String, PathBuf> = HashMap::new();
+        for (spec_path, defines_paths) in pkg_dirs {
+            if let Some(dir) =
+                load_dir(options, spec_path, defines_paths, &read_file, &mut recorder)?
+            {
+                res.insert_dir(dir, &mut defined_at, &mut recorder)?;
             }
         }
+        res.groups = groups::load_groups(path, &mut recorder)?;
+
+        Ok((res, recorder.report))
+    }
 
-        let mut res = Tree {
+    fn empty(root: &Path, options: &LoadOptions) -> Self {
+        Tree {
+            root: root.to_path_buf(),
+            layout: options.layout.clone(),
+            sections: options.sections.clone(),
+            arches: options.arches.clone(),
             packages: HashMap::new(),
-        };
-        for (spec_path, defines_path) in pkg_dirs {
-            let spec = fs::read_to_string(&spec_path)?;
-            let defines = fs::read_to_string(&defines_path)?;
-            let mut context = HashMap::new();
-
-            // First parse spec
-            if let Err(e) = parse(&spec, &mut context) {
-                let e: Vec<String> = e.iter().map(|e| e.to_string()).collect();
-                eprintln!(
-                    "Failed to parse {}: {:?}, skipping.",
-                    spec_path.display(),
-                    e
-                );
+            sources: HashMap::new(),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Add the packages of a loaded directory, skipping names defined before.
+    fn insert_dir(
+        &mut self,
+        dir: LoadedDir,
+        defined_at: &mut HashMap<String, PathBuf>,
+        recorder: &mut Recorder,
+    ) -> Result<(), TreeError> {
+        let mut source = dir.source;
+        for (pkg, defines_path) in dir.packages {
+            if let Some(first_defines_path) = defined_at.get(&pkg.name) {
+                recorder.record(LoadIssue::DuplicatePackage {
+                    name: pkg.name,
+                    defines_path,
+                    first_defines_path: first_defines_path.clone(),
+                })?;
+            } else {
+                source
+                    .subpackages
+                    .push(SubPackage::new(&pkg.name, &defines_path));
+                defined_at.insert(pkg.name.clone(), defines_path);
+                self.packages.insert(pkg.name.clone(), pkg);
+            }
+        }
+
+        if !source.subpackages.is_empty() {
+            source
+                .subpackages
+                .sort_by(|a, b| (a.index, &a.dir).cmp(&(b.index, &b.dir)));
+            self.sources.insert(source.spec_path.clone(), source);
+        }
+
+        Ok(())
+    }
+}
+
+/// A package directory as parsed, before its packages join a tree.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct LoadedDir {
+    source: SourcePackage,
+    /// With the defines each was parsed from
+    packages: Vec<(Package, PathBuf)>,
+}
+
+/// Find every package directory below `path`: spec path -> defines paths sharing it.
+fn walk(
+    path: &Path,
+    layout: &TreeLayout,
+    recorder: &mut Recorder,
+) -> Result<BTreeMap<PathBuf, Vec<PathBuf>>, TreeError> {
+    walk_below(path, path, layout, recorder)
+}
+
+/// Like `walk`, only through `dir`, a directory of the tree at `root`.
+/// Nothing is found if `dir` is gone.
+fn walk_below(
+    root: &Path,
+    dir: &Path,
+    layout: &TreeLayout,
+    recorder: &mut Recorder,
+) -> Result<BTreeMap<PathBuf, Vec<PathBuf>>, TreeError> {
+    let relative = match dir.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return Ok(BTreeMap::new()),
+    };
+    let depth = relative.components().count();
+    let top = relative.components().next();
+    if depth > 3
+        || !dir.is_dir()
+        || top.is_some_and(|c| layout.is_non_package_dir(&c.as_os_str().to_string_lossy()))
+    {
+        return Ok(BTreeMap::new());
+    }
+    let walker = walkdir::WalkDir::new(dir)
+        .max_depth(4 - depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() + depth != 1 || !layout.is_non_package_dir(&e.file_name().to_string_lossy())
+        });
+    let mut pkg_dirs: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for entry in walker {
+        let file = entry?;
+        if file.file_name() == "defines" {
+            let pkg_dir = file
+                .path()
+                .parent()
+                .ok_or_else(|| {
+                    TreeError::FsError(format!(
+                        "The directory of defines file {} is root.",
+                        file.path().display()
+                    ))
+                })?
+                .parent()
+                .ok_or_else(|| {
+                    TreeError::FsError(format!(
+                        "The parent directory of defines file {} is root.",
+                        file.path().display()
+                    ))
+                })?;
+            let spec_path = pkg_dir.join("spec");
+            if !spec_path.is_file() {
+                recorder.record(LoadIssue::MissingSpec {
+                    defines_path: file.path().to_path_buf(),
+                    spec_path,
+                })?;
+                continue;
+            }
+            pkg_dirs
+                .entry(spec_path)
+                .or_default()
+                .push(file.path().to_path_buf());
+        }
+    }
+
+    Ok(pkg_dirs)
+}
+
+/// Reads a `spec` or `defines`, from disk or from elsewhere.
+type ReadFile<'a> = dyn Fn(&Path) -> Result<String, TreeError> + 'a;
+
+fn read_file(path: &Path) -> Result<String, TreeError> {
+    Ok(fs::read_to_string(path)?)
+}
+
+/// Parse one package directory, or `None` if its spec cannot be used.
+fn load_dir(
+    options: &LoadOptions,
+    spec_path: PathBuf,
+    defines_paths: Vec<PathBuf>,
+    read: &ReadFile,
+    recorder: &mut Recorder,
+) -> Result<Option<LoadedDir>, TreeError> {
+    let spec = match read(&spec_path) {
+        Ok(spec) => spec,
+        Err(error) => {
+            recorder.record(LoadIssue::UnreadableFile {
+                path: spec_path,
+                error: error.to_string(),
+            })?;
+            return Ok(None);
+        }
+    };
+    let mut spec_context = HashMap::new();
+
+    // First parse spec, which is shared by every defines in this directory
+    if let Err(errors) = parse(&spec, &mut spec_context) {
+        recorder.record(LoadIssue::ParseError {
+            path: spec_path,
+            errors,
+        })?;
+        return Ok(None);
+    }
+    // Modify context so that defines can understand
+    spec_decorator(&mut spec_context);
+
+    // A malformed source field does not stop the packages from loading
+    let (source, errors) = SourcePackage::from(&spec_context, &spec_path);
+    for error in errors {
+        recorder.record(LoadIssue::SpecError {
+            spec_path: spec_path.clone(),
+            error,
+        })?;
+    }
+    let mut packages = Vec::new();
+    for defines_path in defines_paths {
+        let defines = match read(&defines_path) {
+            Ok(defines) => defines,
+            Err(error) => {
+                recorder.record(LoadIssue::UnreadableFile {
+                    path: defines_path,
+                    error: error.to_string(),
+                })?;
                 continue;
             }
-            // Modify context so that defines can understand
-            spec_decorator(&mut context);
-            // Then parse defines
-            if let Err(e) = parse(&defines, &mut context) {
-                let e: Vec<String> = e.iter().map(|e| e.to_string()).collect();
-                eprintln!(
-                    "Failed to parse {}: {:?}, skipping.",
-                    defines_path.display(),
-                    e
-                );
+        };
+        let mut context = spec_context.clone();
+
+        // Then parse defines
+        if let Err(errors) = parse(&defines, &mut context) {
+            recorder.record(LoadIssue::ParseError {
+                path: defines_path,
+                errors,
+            })?;
+            continue;
+        }
+        // Parse the result into a Package
+        let pkg = match Package::with_registries(
+            &context,
+            &spec_path,
+            &options.layout,
+            &options.sections,
+            &options.arches,
+        ) {
+            Ok(pkg) => pkg,
+            Err(error) => {
+                recorder.record(LoadIssue::PackageError {
+                    defines_path,
+                    error,
+                })?;
                 continue;
             }
-            // Parse the result into a Package
-            let pkg = Package::from(&context, &spec_path)?;
-            if res.packages.contains_key(&pkg.name) {
-                eprintln!(
-                    "Duplicate package name {} found at {}, ignoring.",
-                    &pkg.name,
-                    defines_path.display()
-                );
-            } else {
-                res.packages.insert(pkg.name.clone(), pkg);
+        };
+        packages.push((pkg, defines_path));
+    }
+
+    Ok(Some(LoadedDir { source, packages }))
+}
+
+impl Tree {
+    /// The directory the tree was loaded from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn layout(&self) -> &TreeLayout {
+        &self.layout
+    }
+
+    /// What `PKGSEC` was checked against.
+    pub fn sections(&self) -> &SectionRegistry {
+        &self.sections
+    }
+
+    /// What `FAIL_ARCH` and per-arch fields were read against.
+    pub fn arches(&self) -> &ArchRegistry {
+        &self.arches
+    }
+
+    /// Options to load more of this tree the way it was loaded.
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            policy: LoadPolicy::default(),
+            layout: self.layout.clone(),
+            sections: self.sections.clone(),
+            arches: self.arches.clone(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Package> {
+        self.packages.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Package> {
+        self.packages.values()
+    }
+
+    /// Packages under a category, e.g. `extra` for `extra-admin/packagekit`.
+    pub fn by_category<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| p.category == category)
+    }
+
+    /// Packages under a tree section, e.g. `admin` for `extra-admin/packagekit`.
+    pub fn by_section<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| p.section == section)
+    }
+
+    /// Packages with the given `PKGSEC`.
+    pub fn by_pkg_section<'a>(&'a self, pkg_section: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| p.pkg_section == pkg_section)
+    }
+
+    /// Packages whose spec lives in a directory with the given name, e.g. `packagekit`.
+    pub fn by_directory<'a>(&'a self, directory: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| p.directory == directory)
+    }
+
+    /// Packages whose spec lives somewhere below `path`.
+    pub fn packages_in<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Package> {
+        self.iter()
+            .filter(move |p| Path::new(&p.spec_path).starts_with(path))
+    }
+
+    /// The package directory `name` was built from.
+    pub fn source_of(&self, name: &str) -> Option<&SourcePackage> {
+        self.sources.get(&self.packages.get(name)?.spec_path)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &SourcePackage> {
+        self.sources.values()
+    }
+
+    /// Packages built from the same spec as `name`, including itself, in build order.
+    pub fn siblings_of<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a Package> {
+        self.source_of(name)
+            .into_iter()
+            .flat_map(|s| s.package_names())
+            .filter_map(move |n| self.packages.get(n))
+    }
+
+    /// Packages that list `name` in `PKGPROV` for any architecture.
+    pub fn providers_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| {
+            p.package_provides
+                .values()
+                .flatten()
+                .any(|provided| provided.names().any(|n| n == name))
+        })
+    }
+
+    /// Virtual name -> packages that list it in `PKGPROV` for any architecture.
+    pub fn provider_index(&self) -> HashMap<&str, Vec<&str>> {
+        let mut res: HashMap<&str, Vec<&str>> = HashMap::new();
+        for pkg in self.packages.values() {
+            for provided in pkg
+                .package_provides
+                .values()
+                .flatten()
+                .flat_map(|d| d.names())
+            {
+                let providers = res.entry(provided).or_default();
+                if !providers.contains(&pkg.name.as_str()) {
+                    providers.push(&pkg.name);
+                }
             }
         }
 
-        Ok(res)
+        res
+    }
+
+    /// Packages whose `FAIL_ARCH` does not rule out `arch`.
+    pub fn available_on<'a>(&'a self, arch: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| p.is_available_on(arch))
+    }
+
+    /// Packages whose `FAIL_ARCH` rules out `arch`.
+    pub fn failing_on<'a>(&'a self, arch: &'a str) -> impl Iterator<Item = &'a Package> {
+        self.iter().filter(move |p| !p.is_available_on(arch))
     }
 }
 
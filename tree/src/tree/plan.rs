@@ -0,0 +1,259 @@
+use super::{revdeps::dependency_names, DependencyKind, Tree};
+
+use abbs_meta_scc::tarjan_search;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+/// What to rebuild after some packages changed, and in which order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RebuildPlan {
+    pub arch: Option<String>,
+    pub changed: Vec<String>,
+    /// Everything that has to be rebuilt besides `changed`
+    pub rebuilds: Vec<String>,
+    /// Stage `n` only depends on packages from earlier stages, or on its own cycles
+    pub stages: Vec<BuildStage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BuildStage {
+    /// Strongly connected components of this stage, which can be built in parallel
+    pub groups: Vec<BuildGroup>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BuildGroup {
+    pub packages: Vec<String>,
+    /// The packages depend on each other, and need bootstrapping
+    pub cycle: bool,
+}
+
+impl Tree {
+    /// Plan rebuilding `changed` and everything depending on it through `kinds` on `arch`.
+    ///
+    /// Packages built from the same spec as a rebuilt package are rebuilt too.
+    pub fn plan_rebuild(
+        &self,
+        changed: &[&str],
+        arch: Option<&str>,
+        kinds: &[DependencyKind],
+    ) -> RebuildPlan {
+        let index = self.reverse_dependencies(arch);
+        let mut to_build: BTreeSet<&str> = changed
+            .iter()
+            .copied()
+            .filter(|n| self.packages.contains_key(*n))
+            .collect();
+        to_build.extend(index.transitive_all(changed.iter().copied(), kinds));
+        // Virtual names are not buildable
+        to_build.retain(|n| self.packages.contains_key(*n));
+        let siblings: Vec<&str> = to_build
+            .iter()
+            .flat_map(|n| self.source_of(n))
+            .flat_map(|s| s.package_names())
+            .collect();
+        to_build.extend(siblings);
+
+        let graph = self.dependency_graph(&to_build, arch, kinds);
+        let stages = build_stages(&to_build, graph);
+
+        RebuildPlan {
+            arch: arch.map(|a| a.to_string()),
+            changed: changed.iter().map(|n| n.to_string()).collect(),
+            rebuilds: to_build
+                .iter()
+                .filter(|n| !changed.contains(n))
+                .map(|n| n.to_string())
+                .collect(),
+            stages,
+        }
+    }
+
+    /// Dependencies through `kinds` between packages in `packages`, with
+    /// virtual names resolved to their providers.
+    pub(crate) fn dependency_graph(
+        &self,
+        packages: &BTreeSet<&str>,
+        arch: Option<&str>,
+        kinds: &[DependencyKind],
+    ) -> HashMap<String, Vec<String>> {
+        let providers = self.provider_index();
+        let mut res = HashMap::new();
+        for name in packages.iter() {
+            let pkg = &self.packages[*name];
+            let mut deps = BTreeSet::new();
+            for (kind, field) in [
+                (DependencyKind::Runtime, &pkg.dependencies),
+                (DependencyKind::Build, &pkg.build_dependencies),
+            ] {
+                if !kinds.contains(&kind) {
+                    continue;
+                }
+                for dep in dependency_names(field, arch, &self.arches) {
+                    deps.insert(dep);
+                    deps.extend(providers.get(dep).into_iter().flatten());
+                }
+            }
+            deps.retain(|d| packages.contains(d));
+            res.insert(
+                name.to_string(),
+                deps.into_iter().map(|d| d.to_string()).collect(),
+            );
+        }
+
+        res
+    }
+}
+
+/// Group `packages` into strongly connected components and sort them into stages.
+pub(crate) fn build_stages(
+    packages: &BTreeSet<&str>,
+    graph: HashMap<String, Vec<String>>,
+) -> Vec<BuildStage> {
+    let roots: Vec<String> = packages.iter().map(|n| n.to_string()).collect();
+    let get_deps = move |n: &str| graph.get(n).cloned().unwrap_or_default();
+    // Tarjan's algorithm yields a component only after everything it depends on
+    let sccs = tarjan_search(&roots, &get_deps);
+
+    let mut stage_of: HashMap<&str, usize> = HashMap::new();
+    let mut stages: Vec<BuildStage> = Vec::new();
+    for scc in sccs.iter() {
+        let members: BTreeSet<&str> = scc.iter().map(|n| n.as_str()).collect();
+        let mut stage = 0;
+        let mut cycle = scc.len() > 1;
+        for member in members.iter() {
+            for dep in get_deps(member) {
+                if members.contains(dep.as_str()) {
+                    // Also catches packages depending on themselves
+                    cycle = true;
+                } else if let Some(s) = stage_of.get(dep.as_str()) {
+                    stage = stage.max(s + 1);
+                }
+            }
+        }
+        for member in members.iter() {
+            stage_of.insert(member, stage);
+        }
+
+        if stages.len() <= stage {
+            stages.resize(stage + 1, BuildStage { groups: Vec::new() });
+        }
+        stages[stage].groups.push(BuildGroup {
+            packages: members.iter().map(|n| n.to_string()).collect(),
+            cycle,
+        });
+    }
+
+    stages
+}
+
+impl RebuildPlan {
+    /// Groups that need bootstrap handling.
+    pub fn cycles(&self) -> impl Iterator<Item = &BuildGroup> {
+        self.stages
+            .iter()
+            .flat_map(|s| s.groups.iter())
+            .filter(|g| g.cycle)
+    }
+
+    /// Every package to build, in an order that respects dependencies
+    /// outside of cycles.
+    pub fn build_order(&self) -> impl Iterator<Item = &str> {
+        self.stages
+            .iter()
+            .flat_map(|s| s.groups.iter())
+            .flat_map(|g| g.packages.iter())
+            .map(|n| n.as_str())
+    }
+}
+
+impl fmt::Display for RebuildPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            writeln!(f, "Stage {}:", i + 1)?;
+            for group in stage.groups.iter() {
+                if group.cycle {
+                    writeln!(f, "  [cycle] {}", group.packages.join(" "))?;
+                } else {
+                    writeln!(f, "  {}", group.packages.join(" "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    fn plan(fixture: &Fixture, changed: &[&str]) -> RebuildPlan {
+        fixture
+            .load()
+            .plan_rebuild(changed, None, &DependencyKind::ALL)
+    }
+
+    #[test]
+    fn test_linear_chain() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/lib", "1.0", "")
+            .package("extra-admin/mid", "1.0", "PKGDEP=\"lib\"")
+            .package("extra-admin/app", "1.0", "PKGDEP=\"mid\"")
+            .package("extra-admin/other", "1.0", "");
+
+        let plan = plan(&fixture, &["lib"]);
+        assert_eq!(plan.rebuilds, vec!["app", "mid"]);
+        assert_eq!(
+            plan.to_string(),
+            "Stage 1:\n  lib\nStage 2:\n  mid\nStage 3:\n  app\n"
+        );
+        assert_eq!(
+            plan.build_order().collect::<Vec<_>>(),
+            ["lib", "mid", "app"]
+        );
+    }
+
+    #[test]
+    fn test_diamond() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/base", "1.0", "")
+            .package("extra-admin/left", "1.0", "PKGDEP=\"base\"")
+            .package("extra-admin/right", "1.0", "BUILDDEP=\"base\"")
+            .package("extra-admin/top", "1.0", "PKGDEP=\"left right\"");
+
+        let plan = plan(&fixture, &["base"]);
+        assert_eq!(
+            plan.to_string(),
+            "Stage 1:\n  base\nStage 2:\n  left\n  right\nStage 3:\n  top\n"
+        );
+        assert_eq!(plan.cycles().count(), 0);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/a", "1.0", "PKGDEP=\"b\"")
+            .package("extra-admin/b", "1.0", "BUILDDEP=\"a\"")
+            .package("extra-admin/c", "1.0", "PKGDEP=\"a\"");
+
+        let plan = plan(&fixture, &["a"]);
+        assert_eq!(plan.to_string(), "Stage 1:\n  [cycle] a b\nStage 2:\n  c\n");
+        let cycles: Vec<&BuildGroup> = plan.cycles().collect();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].packages, vec!["a", "b"]);
+
+        // Without build dependencies, there is no cycle left
+        let plan = fixture
+            .load()
+            .plan_rebuild(&["a"], None, &[DependencyKind::Runtime]);
+        assert_eq!(plan.rebuilds, vec!["c"]);
+        assert_eq!(plan.cycles().count(), 0);
+    }
+}
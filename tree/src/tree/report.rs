@@ -0,0 +1,251 @@
+use super::{error::TreeError, TreeLayout};
+use crate::package::{ArchRegistry, PackageError, SectionRegistry};
+use abbs_meta_apml::ParseError;
+use std::{fmt, path::PathBuf};
+
+/// How `Tree::load` reacts when a package cannot be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadPolicy {
+    /// Abort the whole load on the first issue.
+    FailFast,
+    /// Skip the offending package, record the issue and keep going.
+    #[default]
+    CollectAll,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub policy: LoadPolicy,
+    pub layout: TreeLayout,
+    /// What `PKGSEC` may be
+    pub sections: SectionRegistry,
+    /// Arches and arch groups `FAIL_ARCH` and per-arch fields refer to
+    pub arches: ArchRegistry,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            policy: LoadPolicy::default(),
+            layout: TreeLayout::default(),
+            sections: SectionRegistry::system().clone(),
+            arches: ArchRegistry::system().clone(),
+        }
+    }
+}
+
+/// Applies a `LoadPolicy` to issues as they come up.
+pub(crate) struct Recorder {
+    policy: LoadPolicy,
+    pub report: TreeLoadReport,
+}
+
+impl Recorder {
+    pub fn new(policy: LoadPolicy) -> Self {
+        Recorder {
+            policy,
+            report: TreeLoadReport::default(),
+        }
+    }
+
+    pub fn record(&mut self, issue: LoadIssue) -> Result<(), TreeError> {
+        match self.policy {
+            LoadPolicy::FailFast => Err(TreeError::LoadError(Box::new(issue))),
+            LoadPolicy::CollectAll => {
+                self.report.issues.push(issue);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A single problem encountered while loading a tree.
+#[derive(Debug, Clone)]
+pub enum LoadIssue {
+    /// A `defines` file has no `spec` next to its package directory.
+    MissingSpec {
+        defines_path: PathBuf,
+        spec_path: PathBuf,
+    },
+    /// A `spec` or `defines` file cannot be read as text.
+    UnreadableFile { path: PathBuf, error: String },
+    /// A `spec` or `defines` file is not valid APML.
+    ParseError {
+        path: PathBuf,
+        errors: Vec<ParseError>,
+    },
+    /// The spec parsed, but its source fields are invalid.
+    SpecError {
+        spec_path: PathBuf,
+        error: PackageError,
+    },
+    /// The files parsed, but do not describe a valid package.
+    PackageError {
+        defines_path: PathBuf,
+        error: PackageError,
+    },
+    /// Another `defines` file already declared a package with this name.
+    DuplicatePackage {
+        name: String,
+        defines_path: PathBuf,
+        first_defines_path: PathBuf,
+    },
+    /// A file under `groups/` cannot be read as text.
+    UnreadableGroup { path: PathBuf, error: String },
+}
+
+impl LoadIssue {
+    /// The file this issue should be reported against.
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            LoadIssue::MissingSpec { defines_path, .. } => defines_path,
+            LoadIssue::UnreadableFile { path, .. } => path,
+            LoadIssue::ParseError { path, .. } => path,
+            LoadIssue::SpecError { spec_path, .. } => spec_path,
+            LoadIssue::PackageError { defines_path, .. } => defines_path,
+            LoadIssue::DuplicatePackage { defines_path, .. } => defines_path,
+            LoadIssue::UnreadableGroup { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for LoadIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadIssue::MissingSpec {
+                defines_path,
+                spec_path,
+            } => write!(
+                f,
+                "spec file not found at {} for {}",
+                spec_path.display(),
+                defines_path.display()
+            ),
+            LoadIssue::UnreadableFile { path, error } => {
+                write!(f, "Failed to read {}: {}", path.display(), error)
+            }
+            LoadIssue::ParseError { path, errors } => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Failed to parse {}: {:?}", path.display(), errors)
+            }
+            LoadIssue::SpecError { spec_path, error } => {
+                write!(f, "{} ({})", error, spec_path.display())
+            }
+            LoadIssue::PackageError {
+                defines_path,
+                error,
+            } => write!(f, "{} ({})", error, defines_path.display()),
+            LoadIssue::DuplicatePackage {
+                name,
+                defines_path,
+                first_defines_path,
+            } => write!(
+                f,
+                "Duplicate package name {} found at {}, first defined at {}",
+                name,
+                defines_path.display(),
+                first_defines_path.display()
+            ),
+            LoadIssue::UnreadableGroup { path, error } => {
+                write!(f, "Failed to read group {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+/// Everything that went wrong during a `Tree::load`.
+#[derive(Debug, Clone, Default)]
+pub struct TreeLoadReport {
+    pub issues: Vec<LoadIssue>,
+}
+
+impl TreeLoadReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn parse_errors(&self) -> impl Iterator<Item = &LoadIssue> {
+        self.issues
+            .iter()
+            .filter(|i| matches!(i, LoadIssue::ParseError { .. }))
+    }
+
+    pub fn package_errors(&self) -> impl Iterator<Item = &LoadIssue> {
+        self.issues.iter().filter(|i| {
+            matches!(
+                i,
+                LoadIssue::SpecError { .. } | LoadIssue::PackageError { .. }
+            )
+        })
+    }
+
+    pub fn duplicates(&self) -> impl Iterator<Item = &LoadIssue> {
+        self.issues
+            .iter()
+            .filter(|i| matches!(i, LoadIssue::DuplicatePackage { .. }))
+    }
+
+    pub fn missing_specs(&self) -> impl Iterator<Item = &LoadIssue> {
+        self.issues
+            .iter()
+            .filter(|i| matches!(i, LoadIssue::MissingSpec { .. }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{fixture::Fixture, Tree};
+    use std::fs;
+
+    #[test]
+    fn test_unreadable_file() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .package("extra-admin/bar", "1.0", "");
+        let defines_path = fixture.path("extra-admin/bar/autobuild/defines");
+        fs::write(&defines_path, b"PKGNAME=bar\nPKGDES=\"\xff\xfe\"\n").unwrap();
+
+        let (tree, report) = Tree::load(fixture.root(), &Fixture::options()).unwrap();
+        assert!(tree.get("foo").is_some());
+        assert!(tree.get("bar").is_none());
+        match report.issues.as_slice() {
+            [issue @ LoadIssue::UnreadableFile { .. }] => {
+                assert_eq!(issue.path(), &defines_path)
+            }
+            issues => panic!("unexpected issues: {:?}", issues),
+        }
+
+        let options = LoadOptions {
+            policy: LoadPolicy::FailFast,
+            ..Fixture::options()
+        };
+        assert!(Tree::load(fixture.root(), &options).is_err());
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .package("ports-admin/bar", "1.0", "");
+        let options = LoadOptions {
+            layout: TreeLayout::default().with_categories(&["extra", "ports"]),
+            ..Fixture::options()
+        };
+
+        let (tree, report) = Tree::load(fixture.root(), &options).unwrap();
+        assert!(report.is_empty());
+        let bar = tree.get("bar").unwrap();
+        assert_eq!(
+            (bar.category.as_str(), bar.section.as_str()),
+            ("ports", "admin")
+        );
+        assert_eq!(tree.get("foo").unwrap().category, "extra");
+
+        // Not a category in the default layout
+        let tree = fixture.load();
+        assert_eq!(tree.get("bar").unwrap().category, "");
+    }
+}
@@ -0,0 +1,200 @@
+use super::Tree;
+use crate::package::{deps_for_arch, ArchRegistry, Package, PackageDepDependencies};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DependencyKind {
+    /// `PKGDEP`
+    Runtime,
+    /// `BUILDDEP`
+    Build,
+}
+
+impl DependencyKind {
+    pub const ALL: [DependencyKind; 2] = [DependencyKind::Runtime, DependencyKind::Build];
+}
+
+/// Which packages depend on a given package.
+///
+/// Dependencies on a virtual name count as dependencies on every package
+/// providing it through `PKGPROV`, and are also indexed under the virtual name.
+#[derive(Debug, Clone)]
+pub struct ReverseDependencyIndex {
+    /// `None` if this index covers every arch
+    arch: Option<String>,
+    /// dependency -> [(dependent, kind)]
+    edges: HashMap<String, Vec<(String, DependencyKind)>>,
+}
+
+impl Tree {
+    /// Build a reverse dependency index for `arch`, or the union of all arches if `None`.
+    pub fn reverse_dependencies(&self, arch: Option<&str>) -> ReverseDependencyIndex {
+        let providers = self.provider_index();
+        let providers_of = |name: &str| -> Vec<String> {
+            providers
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|p| p.to_string())
+                .collect()
+        };
+        let mut res = ReverseDependencyIndex {
+            arch: arch.map(|a| a.to_string()),
+            edges: HashMap::new(),
+        };
+        for pkg in self.packages.values() {
+            res.insert(pkg, &self.arches, &providers_of);
+        }
+
+        res
+    }
+}
+
+impl ReverseDependencyIndex {
+    /// Index the dependencies of `pkg`, with `providers_of` giving the
+    /// packages providing a virtual name.
+    fn insert(
+        &mut self,
+        pkg: &Package,
+        arches: &ArchRegistry,
+        providers_of: &dyn Fn(&str) -> Vec<String>,
+    ) {
+        if let Some(arch) = &self.arch {
+            if !pkg.is_available_on(arch) {
+                return;
+            }
+        }
+        for (kind, deps) in [
+            (DependencyKind::Runtime, &pkg.dependencies),
+            (DependencyKind::Build, &pkg.build_dependencies),
+        ] {
+            for name in dependency_names(deps, self.arch.as_deref(), arches) {
+                let mut targets: BTreeSet<String> = BTreeSet::new();
+                targets.insert(name.to_string());
+                targets.extend(providers_of(name));
+                for target in targets {
+                    let dependents = self.edges.entry(target).or_default();
+                    if !dependents.contains(&(pkg.name.clone(), kind)) {
+                        dependents.push((pkg.name.clone(), kind));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Index `dependents` again as they are in `tree`, after they or the
+    /// providers of something they depend on changed. Those gone from `tree`
+    /// are dropped.
+    #[cfg(feature = "watch")]
+    pub(crate) fn reindex(
+        &mut self,
+        tree: &Tree,
+        dependents: &BTreeSet<String>,
+        providers_of: &dyn Fn(&str) -> Vec<String>,
+    ) {
+        for entries in self.edges.values_mut() {
+            entries.retain(|(dependent, _)| !dependents.contains(dependent));
+        }
+        self.edges.retain(|_, entries| !entries.is_empty());
+        for name in dependents {
+            if let Some(pkg) = tree.get(name) {
+                self.insert(pkg, &tree.arches, providers_of);
+            }
+        }
+    }
+
+    pub fn arch(&self) -> Option<&str> {
+        self.arch.as_deref()
+    }
+
+    /// Packages depending on `name` directly through one of `kinds`.
+    pub fn direct(&self, name: &str, kinds: &[DependencyKind]) -> BTreeSet<&str> {
+        self.edges
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|(_, kind)| kinds.contains(kind))
+            .map(|(dependent, _)| dependent.as_str())
+            .collect()
+    }
+
+    /// Packages depending on `name` directly or through other packages,
+    /// following only edges of `kinds`. `name` itself is not included unless it
+    /// is part of a dependency cycle.
+    pub fn transitive(&self, name: &str, kinds: &[DependencyKind]) -> BTreeSet<&str> {
+        let mut res = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(name);
+        while let Some(current) = queue.pop_front() {
+            for dependent in self.direct(current, kinds) {
+                if res.insert(dependent) {
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Like `transitive`, for several packages at once.
+    pub fn transitive_all<'a, I>(&'a self, names: I, kinds: &[DependencyKind]) -> BTreeSet<&'a str>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut res = BTreeSet::new();
+        for name in names {
+            res.extend(self.transitive(name, kinds));
+        }
+
+        res
+    }
+}
+
+/// Dependency names that apply on `arch`, or on any arch if `None`.
+pub(crate) fn dependency_names<'a>(
+    deps: &'a PackageDepDependencies,
+    arch: Option<&str>,
+    arches: &ArchRegistry,
+) -> BTreeSet<&'a str> {
+    match arch {
+        Some(arch) => deps_for_arch(deps, arch, arches)
+            .iter()
+            .flat_map(|d| d.names())
+            .collect(),
+        None => deps.values().flatten().flat_map(|d| d.names()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::fixture::Fixture;
+
+    #[test]
+    fn test_reverse_dependencies() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/app", "1.0", "PKGDEP=\"libssl|libressl\"")
+            .package("extra-admin/tool", "1.0", "BUILDDEP=\"app\"")
+            .package("core-libs/openssl", "3.0", "PKGPROV=\"libssl\"")
+            .package("core-libs/libressl", "3.8", "");
+        let index = fixture.load().reverse_dependencies(None);
+
+        let runtime = [DependencyKind::Runtime];
+        let set = |names: &[&'static str]| names.iter().copied().collect::<BTreeSet<&str>>();
+        // Both alternatives, the virtual name and its provider
+        assert_eq!(index.direct("libressl", &runtime), set(&["app"]));
+        assert_eq!(index.direct("libssl", &runtime), set(&["app"]));
+        assert_eq!(index.direct("openssl", &runtime), set(&["app"]));
+        assert_eq!(index.direct("app", &runtime), set(&[]));
+        assert_eq!(index.direct("app", &DependencyKind::ALL), set(&["tool"]));
+
+        assert_eq!(index.transitive("openssl", &runtime), set(&["app"]));
+        assert_eq!(
+            index.transitive("openssl", &DependencyKind::ALL),
+            set(&["app", "tool"])
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use super::{DependencyKind, FullVersion, RebuildPlan, Tree, TreeDiff, VersionChange};
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+/// What a topic branch does compared to where it branched off stable.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TopicReport {
+    /// Packages the topic adds, removes or changes
+    pub touched: Vec<String>,
+    /// Touched packages older in the topic than in stable, from the stable
+    /// version to the topic one
+    pub regressions: Vec<VersionChange>,
+    /// Rebuilding `touched` and everything depending on it, in the topic tree
+    pub rebuild: RebuildPlan,
+    /// From the merge-base to the topic
+    pub diff: TreeDiff,
+}
+
+impl Tree {
+    /// Compare a `topic` tree against `stable`, with this tree being their
+    /// merge-base. Only what changed since the merge-base counts as touched by
+    /// the topic, while regressions are against `stable` as it is now; see
+    /// `Tree::compare_topic_git` to find the merge-base of two revisions.
+    pub fn compare_topic(&self, stable: &Tree, topic: &Tree) -> TopicReport {
+        let diff = self.diff(topic);
+        let mut touched: BTreeSet<&str> = diff.changed_packages();
+        touched.extend(diff.added.iter().map(|n| n.as_str()));
+        touched.extend(diff.removed.iter().map(|n| n.as_str()));
+
+        let regressions = touched
+            .iter()
+            .filter_map(|name| match (stable.get(name), topic.get(name)) {
+                (Some(old), Some(new)) if new.package_version() < old.package_version() => {
+                    Some(VersionChange {
+                        name: name.to_string(),
+                        old: FullVersion::of(old),
+                        new: FullVersion::of(new),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+
+        let touched: Vec<&str> = touched.into_iter().collect();
+        let rebuild = topic.plan_rebuild(&touched, None, &DependencyKind::ALL);
+
+        TopicReport {
+            touched: touched.iter().map(|n| n.to_string()).collect(),
+            regressions,
+            rebuild,
+            diff,
+        }
+    }
+}
+
+impl fmt::Display for TopicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Touched: {}", self.touched.join(" "))?;
+        for c in self.regressions.iter() {
+            writeln!(f, "Regression: {} {} -> {}", c.name, c.old, c.new)?;
+        }
+        if !self.rebuild.rebuilds.is_empty() {
+            writeln!(f, "Also rebuild: {}", self.rebuild.rebuilds.join(" "))?;
+        }
+        writeln!(f)?;
+        write!(f, "{}", self.diff)?;
+        writeln!(f)?;
+        write!(f, "{}", self.rebuild)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::fixture::Fixture;
+
+    #[test]
+    fn test_compare_topic() {
+        let base = Fixture::new();
+        base.package("extra-admin/foo", "1.0", "")
+            .package("extra-admin/bar", "2.0", "")
+            .package("extra-admin/app", "1.0", "PKGDEP=\"foo\"")
+            .package("extra-admin/other", "1.0", "");
+        // Stable moves on after the topic branched off
+        let stable = Fixture::new();
+        stable
+            .package("extra-admin/foo", "1.0", "")
+            .package("extra-admin/bar", "2.0", "")
+            .package("extra-admin/app", "1.0", "PKGDEP=\"foo\"")
+            .package("extra-admin/other", "2.0", "");
+        let topic = Fixture::new();
+        topic
+            .package("extra-admin/foo", "1.1", "")
+            .package("extra-admin/bar", "1.9", "")
+            .package("extra-admin/app", "1.0", "PKGDEP=\"foo\"")
+            .package("extra-admin/other", "1.0", "");
+
+        let report = base.load().compare_topic(&stable.load(), &topic.load());
+        // other is only older than in stable because the topic did not pick
+        // up the update, which merging keeps
+        assert_eq!(report.touched, vec!["bar", "foo"]);
+        let regressions: Vec<String> = report
+            .regressions
+            .iter()
+            .map(|c| format!("{} {} -> {}", c.name, c.old, c.new))
+            .collect();
+        assert_eq!(regressions, vec!["bar 2.0 -> 1.9"]);
+        assert_eq!(report.rebuild.rebuilds, vec!["app"]);
+    }
+}
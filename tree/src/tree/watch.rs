@@ -0,0 +1,488 @@
+use super::{
+    error::TreeError, groups::load_groups, load_dir, read_file, report::Recorder, walk_below,
+    DependencyKind, LoadIssue, LoadPolicy, ReverseDependencyIndex, Tree, TreeDiff, GROUPS_DIR,
+};
+use crate::package::Package;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+/// Editors save in bursts, so wait this long for things to settle before reloading.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A tree kept in sync with the files it was loaded from, along with its indexes.
+pub struct WatchedTree {
+    pub tree: Tree,
+    /// Across all arches
+    pub reverse_dependencies: ReverseDependencyIndex,
+    /// Virtual name -> packages providing it
+    pub providers: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TreeEvent {
+    /// Package directories were reloaded, with what that changed
+    Changed {
+        spec_paths: Vec<PathBuf>,
+        diff: TreeDiff,
+    },
+    /// Group files were reloaded, and differ from before
+    GroupsChanged,
+    /// A reloaded package or group could not be loaded, and is gone from the tree
+    Issue(LoadIssue),
+    /// The watch itself ran into trouble
+    Error(String),
+}
+
+/// Stops watching when dropped.
+pub struct TreeWatcher {
+    state: Arc<RwLock<WatchedTree>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl TreeWatcher {
+    pub fn state(&self) -> Arc<RwLock<WatchedTree>> {
+        self.state.clone()
+    }
+}
+
+impl WatchedTree {
+    fn new(tree: Tree) -> Self {
+        let mut res = WatchedTree {
+            reverse_dependencies: tree.reverse_dependencies(None),
+            providers: HashMap::new(),
+            tree,
+        };
+        res.reindex();
+        res
+    }
+
+    fn reindex(&mut self) {
+        self.reverse_dependencies = self.tree.reverse_dependencies(None);
+        self.providers = self
+            .tree
+            .provider_index()
+            .into_iter()
+            .map(|(name, providers)| {
+                (
+                    name.to_string(),
+                    providers.into_iter().map(|p| p.to_string()).collect(),
+                )
+            })
+            .collect();
+    }
+
+    /// Bring the indexes up to date after the packages of `old` were taken out
+    /// of the tree and those of `new` put in, without going over the whole tree.
+    fn update_indexes(&mut self, old: &Tree, new: &Tree) {
+        let provided_names = |pkg: &Package| -> BTreeSet<String> {
+            pkg.package_provides
+                .values()
+                .flatten()
+                .flat_map(|d| d.names())
+                .map(|n| n.to_string())
+                .collect()
+        };
+        let mut dependents: BTreeSet<String> = BTreeSet::new();
+        let mut virtual_names: BTreeSet<String> = BTreeSet::new();
+        for pkg in old.packages.values() {
+            for provided in provided_names(pkg) {
+                if let Some(providers) = self.providers.get_mut(&provided) {
+                    providers.retain(|p| p != &pkg.name);
+                    if providers.is_empty() {
+                        self.providers.remove(&provided);
+                    }
+                }
+                virtual_names.insert(provided);
+            }
+            dependents.insert(pkg.name.clone());
+        }
+        for pkg in new.packages.values() {
+            for provided in provided_names(pkg) {
+                let providers = self.providers.entry(provided.clone()).or_default();
+                if !providers.contains(&pkg.name) {
+                    providers.push(pkg.name.clone());
+                }
+                virtual_names.insert(provided);
+            }
+            dependents.insert(pkg.name.clone());
+        }
+        // Whatever depends on a name whose providers changed resolves differently now
+        for name in virtual_names.iter() {
+            dependents.extend(
+                self.reverse_dependencies
+                    .direct(name, &DependencyKind::ALL)
+                    .into_iter()
+                    .map(|d| d.to_string()),
+            );
+        }
+
+        let providers = &self.providers;
+        self.reverse_dependencies
+            .reindex(&self.tree, &dependents, &|name| {
+                providers.get(name).cloned().unwrap_or_default()
+            });
+    }
+}
+
+impl Tree {
+    /// Watch the directory this tree was loaded from, and reload packages whose
+    /// `spec` or `defines` change, and groups when anything under `groups/` does. Changes are announced on the returned channel.
+    pub fn watch(self) -> Result<(TreeWatcher, mpsc::Receiver<TreeEvent>), TreeError> {
+        let root = self.root.clone();
+        let (fs_tx, fs_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let _ = fs_tx.send(res);
+        })
+        .map_err(|e| TreeError::FsError(e.to_string()))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| TreeError::FsError(e.to_string()))?;
+
+        let state = Arc::new(RwLock::new(WatchedTree::new(self)));
+        let (tx, rx) = mpsc::channel();
+        let worker_state = state.clone();
+        thread::spawn(move || {
+            // Ends once the watcher, and with it the sending half, is dropped
+            while let Ok(res) = fs_rx.recv() {
+                let mut events = vec![res];
+                while let Ok(res) = fs_rx.recv_timeout(DEBOUNCE) {
+                    events.push(res);
+                }
+
+                let mut scopes = BTreeSet::new();
+                for event in events {
+                    match event {
+                        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                            scopes.extend(event.paths.iter().filter_map(|p| scope_of(&root, p)));
+                        }
+                        Ok(_) => (),
+                        Err(e) => {
+                            let _ = tx.send(TreeEvent::Error(e.to_string()));
+                        }
+                    }
+                }
+                if scopes.is_empty() {
+                    continue;
+                }
+
+                let mut state = match worker_state.write() {
+                    Ok(state) => state,
+                    Err(_) => return,
+                };
+                let events = match reload(&mut state, &scopes) {
+                    Ok(events) => events,
+                    Err(e) => vec![TreeEvent::Error(e.to_string())],
+                };
+                drop(state);
+                for event in events {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok((
+            TreeWatcher {
+                state,
+                _watcher: watcher,
+            },
+            rx,
+        ))
+    }
+}
+
+/// The directory whose packages a change under `root` may affect: a package
+/// directory, or a whole `category-section` directory. Nothing below a
+/// dot-directory such as `.git` does.
+fn scope_of(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components: Vec<_> = relative.components().collect();
+    if components
+        .iter()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+    {
+        return None;
+    }
+    match components.len() {
+        0 => None,
+        // A category-section or package directory itself
+        1 | 2 => Some(root.join(relative)),
+        _ => {
+            let name = path.file_name()?;
+            // The spec, a defines or a directory holding one
+            if name == "spec" || name == "defines" || components.len() == 3 {
+                Some(root.join(components[0]).join(components[1]))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Reload every package directory below `scopes`, and the groups if a scope is
+/// below `groups/`. Only the scopes are walked, and only what changed is reindexed.
+fn reload(
+    state: &mut WatchedTree,
+    scopes: &BTreeSet<PathBuf>,
+) -> Result<Vec<TreeEvent>, TreeError> {
+    let tree = &mut state.tree;
+    let root = tree.root.clone();
+    let options = tree.load_options();
+    let mut recorder = Recorder::new(LoadPolicy::CollectAll);
+    let in_scope = |p: &Path| scopes.iter().any(|s| p.starts_with(s));
+
+    let mut pkg_dirs = BTreeMap::new();
+    // A scope inside another one is walked with it
+    for scope in scopes.iter().filter(|s| {
+        !scopes
+            .iter()
+            .any(|other| other != *s && s.starts_with(other))
+    }) {
+        pkg_dirs.extend(walk_below(&root, scope, &options.layout, &mut recorder)?);
+    }
+
+    let mut spec_paths: BTreeSet<PathBuf> = pkg_dirs.keys().cloned().collect();
+    spec_paths.extend(
+        tree.sources
+            .keys()
+            .map(PathBuf::from)
+            .filter(|p| in_scope(p)),
+    );
+
+    // Take out everything in the scopes first, so that a package moving between
+    // directories is not mistaken for a duplicate
+    let mut old = Tree::empty(&root, &options);
+    for spec_path in spec_paths.iter() {
+        let spec_path = spec_path.to_string_lossy().to_string();
+        if let Some(source) = tree.sources.remove(&spec_path) {
+            for name in source.package_names() {
+                if let Some(pkg) = tree.packages.remove(name) {
+                    old.packages.insert(name.to_string(), pkg);
+                }
+            }
+        }
+    }
+
+    let mut defined_at: HashMap<String, PathBuf> = tree
+        .sources
+        .values()
+        .flat_map(|s| s.subpackages.iter())
+        .map(|s| (s.name.clone(), PathBuf::from(&s.defines_path)))
+        .collect();
+    let mut new = Tree::empty(&root, &options);
+    for (spec_path, defines_paths) in pkg_dirs {
+        if let Some(dir) = load_dir(
+            &options,
+            spec_path,
+            defines_paths,
+            &read_file,
+            &mut recorder,
+        )? {
+            let source = dir.source.spec_path.clone();
+            tree.insert_dir(dir, &mut defined_at, &mut recorder)?;
+            if let Some(source) = tree.sources.get(&source) {
+                for name in source.package_names() {
+                    new.packages
+                        .insert(name.to_string(), tree.packages[name].clone());
+                }
+            }
+        }
+    }
+
+    // There are few group files, so reload them all
+    let mut groups_changed = false;
+    let groups_dir = root.join(GROUPS_DIR);
+    if scopes.iter().any(|s| s.starts_with(&groups_dir)) {
+        let groups = load_groups(&root, &mut recorder)?;
+        groups_changed = groups != tree.groups;
+        tree.groups = groups;
+    }
+    state.update_indexes(&old, &new);
+
+    let mut res: Vec<TreeEvent> = recorder
+        .report
+        .issues
+        .into_iter()
+        .map(TreeEvent::Issue)
+        .collect();
+    let diff = old.diff(&new);
+    if !diff.is_empty() {
+        res.push(TreeEvent::Changed {
+            spec_paths: spec_paths.into_iter().collect(),
+            diff,
+        });
+    }
+    if groups_changed {
+        res.push(TreeEvent::GroupsChanged);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{fixture::Fixture, DependencyKind, PackageMove};
+
+    fn scopes(fixture: &Fixture, paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths
+            .iter()
+            .filter_map(|p| scope_of(fixture.root(), &fixture.path(p)))
+            .collect()
+    }
+
+    #[test]
+    fn test_reload() {
+        let fixture = Fixture::new();
+        fixture.package("extra-admin/foo", "1.0", "").package(
+            "extra-admin/bar",
+            "1.0",
+            "PKGDEP=\"foo\"",
+        );
+        let mut state = WatchedTree::new(fixture.load());
+
+        // Moving a package is one change, not a removal and a duplicate
+        fixture
+            .remove("extra-admin/foo")
+            .package("app-admin/foo", "1.0", "");
+        let events = reload(
+            &mut state,
+            &scopes(&fixture, &["extra-admin/foo", "app-admin/foo/spec"]),
+        )
+        .unwrap();
+        match events.as_slice() {
+            [TreeEvent::Changed { diff, .. }] => assert_eq!(
+                diff.moves,
+                vec![PackageMove {
+                    name: "foo".to_string(),
+                    old: "extra-admin/foo".to_string(),
+                    new: "app-admin/foo".to_string(),
+                }]
+            ),
+            events => panic!("unexpected events: {:?}", events),
+        }
+        assert_eq!(state.tree.get("foo").unwrap().category, "app");
+        assert_eq!(
+            state
+                .reverse_dependencies
+                .direct("foo", &[DependencyKind::Runtime]),
+            BTreeSet::from(["bar"])
+        );
+
+        // A package that fails to load is reported and gone
+        fixture.write(
+            "extra-admin/bar/autobuild/defines",
+            "PKGNAME=bar\nPKGDES=\"bar\"\n",
+        );
+        let events = reload(
+            &mut state,
+            &scopes(&fixture, &["extra-admin/bar/autobuild/defines"]),
+        )
+        .unwrap();
+        match events.as_slice() {
+            [TreeEvent::Issue(LoadIssue::PackageError { .. }), TreeEvent::Changed { diff, .. }] => {
+                assert_eq!(diff.removed, vec!["bar"])
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+        assert!(state.tree.get("bar").is_none());
+        assert!(state
+            .reverse_dependencies
+            .direct("foo", &[DependencyKind::Runtime])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reload_groups() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/foo", "1.0", "")
+            .write("groups/admin", "foo\n");
+        let mut state = WatchedTree::new(fixture.load());
+
+        fixture.write("groups/admin", "foo\nbar\n");
+        let events = reload(&mut state, &scopes(&fixture, &["groups/admin"])).unwrap();
+        assert!(matches!(events.as_slice(), [TreeEvent::GroupsChanged]));
+        assert_eq!(state.tree.group("admin").unwrap().entries.len(), 2);
+
+        fixture.remove("groups/admin");
+        let events = reload(&mut state, &scopes(&fixture, &["groups/admin"])).unwrap();
+        assert!(matches!(events.as_slice(), [TreeEvent::GroupsChanged]));
+        assert!(state.tree.group("admin").is_none());
+    }
+
+    #[test]
+    fn test_reload_providers() {
+        let fixture = Fixture::new();
+        fixture
+            .package("extra-admin/app", "1.0", "PKGDEP=\"ssl\"")
+            .package("core-libs/openssl", "3.0", "PKGPROV=\"ssl\"")
+            .package("core-libs/libressl", "3.8", "");
+        let mut state = WatchedTree::new(fixture.load());
+        let runtime = [DependencyKind::Runtime];
+        assert_eq!(
+            state.reverse_dependencies.direct("openssl", &runtime),
+            BTreeSet::from(["app"])
+        );
+
+        // Only the providers changed, but app now resolves to libressl
+        fixture.package("core-libs/openssl", "3.0", "").package(
+            "core-libs/libressl",
+            "3.8",
+            "PKGPROV=\"ssl\"",
+        );
+        reload(
+            &mut state,
+            &scopes(&fixture, &["core-libs/openssl", "core-libs/libressl"]),
+        )
+        .unwrap();
+        assert_eq!(state.providers["ssl"], vec!["libressl"]);
+        let fresh = state.tree.reverse_dependencies(None);
+        for name in ["app", "ssl", "openssl", "libressl"] {
+            assert_eq!(
+                state.reverse_dependencies.direct(name, &runtime),
+                fresh.direct(name, &runtime),
+                "{}",
+                name
+            );
+        }
+        assert!(state
+            .reverse_dependencies
+            .direct("openssl", &runtime)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_ignore_dot_dirs() {
+        let fixture = Fixture::new();
+        fixture.package("extra-admin/foo", "1.0", "");
+        assert!(scopes(
+            &fixture,
+            &[
+                ".git/objects/ab/cdef0123",
+                ".git/index",
+                "extra-admin/.foo.spec.swp",
+                "extra-admin/foo/.spec.swp",
+            ]
+        )
+        .is_empty());
+
+        let (_watcher, rx) = fixture.load().watch().unwrap();
+        fixture.write(".git/objects/ab/cdef0123", "blob");
+        assert!(rx.recv_timeout(DEBOUNCE * 5).is_err());
+        // The watch does work
+        fixture.package("extra-admin/foo", "1.1", "");
+        match rx.recv_timeout(DEBOUNCE * 25) {
+            Ok(TreeEvent::Changed { diff, .. }) => assert_eq!(diff.version_changes.len(), 1),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+}